@@ -0,0 +1,96 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Memory that frames waiting for the GIF encoder may take up before new ones are dropped
+/// instead of stalling the event loop.
+const RECORDING_QUEUE_BYTES: usize = 256 * 1024 * 1024;
+/// NeuQuant sampling speed for GIF palettes, from 1 (best) to 30 (fastest).
+const GIF_QUANTIZE_SPEED: i32 = 10;
+
+/// A captured frame as tightly packed RGBA rows, top row first.
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+/// Encodes the image as a PNG on a background thread and reports the result on stdout. Join
+/// the returned handle before exiting, or the file may be cut short.
+pub fn save_png(image: Image, path: String) -> JoinHandle<()> {
+    return thread::spawn(move || match write_png(&image, &path) {
+        Ok(()) => println!("Saved {}", path),
+        Err(err) => eprintln!("Failed to save {}: {}", path, err),
+    });
+}
+
+fn write_png(image: &Image, path: &str) -> anyhow::Result<()> {
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), image.width, image.height);
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&image.rgba)?;
+    return Ok(());
+}
+
+/// An animated GIF being written by a background thread. Frames are queued with `push`, and
+/// the file is complete once the handle returned by `finish` has been joined.
+pub struct Recording {
+    pub path: String,
+    width: u32,
+    height: u32,
+    frames: SyncSender<(Image, Duration)>,
+    encoder: JoinHandle<()>,
+    /// Frames skipped because the encoder fell behind or the window changed size.
+    dropped: u32,
+}
+
+impl Recording {
+    pub fn start(path: String, width: u32, height: u32) -> anyhow::Result<Recording> {
+        let mut encoder = gif::Encoder::new(BufWriter::new(File::create(&path)?), width as u16, height as u16, &[])?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+
+        let frame_bytes = (width as usize * height as usize * 4).max(1);
+        let (frames, queue) = mpsc::sync_channel::<(Image, Duration)>((RECORDING_QUEUE_BYTES / frame_bytes).max(1));
+        let thread_path = path.clone();
+        let encoder = thread::spawn(move || {
+            let result: anyhow::Result<()> = queue.iter().try_for_each(|(mut image, delay)| {
+                let mut frame = gif::Frame::from_rgba_speed(image.width as u16, image.height as u16, &mut image.rgba, GIF_QUANTIZE_SPEED);
+                // GIF delays are in hundredths of a second.
+                frame.delay = (delay.as_millis() / 10).min(u16::MAX as u128) as u16;
+                encoder.write_frame(&frame)?;
+                Ok(())
+            });
+            match result {
+                Ok(()) => println!("Saved {}", thread_path),
+                Err(err) => eprintln!("Failed to save {}: {}", thread_path, err),
+            }
+        });
+        return Ok(Recording { path, width, height, frames, encoder, dropped: 0 });
+    }
+
+    /// Queues a frame that stays on screen for `delay` when the GIF plays back.
+    pub fn push(&mut self, image: Image, delay: Duration) {
+        if image.width != self.width || image.height != self.height {
+            self.dropped += 1;
+            return;
+        }
+        match self.frames.try_send((image, delay)) {
+            Ok(()) => (),
+            Err(TrySendError::Full(_)) => self.dropped += 1,
+            // The encoder stopped after an error it has already reported.
+            Err(TrySendError::Disconnected(_)) => (),
+        }
+    }
+
+    /// Stops taking frames. The encoder finishes the file once the queue has drained; join the
+    /// returned handle to wait for that.
+    pub fn finish(self) -> JoinHandle<()> {
+        if self.dropped > 0 {
+            eprintln!("{} frames were left out of {}", self.dropped, self.path);
+        }
+        drop(self.frames);
+        return self.encoder;
+    }
+}
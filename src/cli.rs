@@ -0,0 +1,193 @@
+use crate::{HISTORY_LENGTH, MAX_ZOOM, MIN_ZOOM, SQUARE_SIZE};
+use clap::{App, Arg};
+use gol_nvg::engine::EngineKind;
+use gol_nvg::rule::Rule;
+use gol_nvg::Boundary;
+use std::str::FromStr;
+
+/// Startup settings taken from the command line.
+pub struct Options {
+    /// Run this many generations without drawing them and report timings.
+    pub bench: Option<u64>,
+    /// Step the dense engine on one thread, e.g. to compare against the parallel timings.
+    pub serial: bool,
+    /// Draw the universe in the terminal instead of opening a window.
+    pub headless: bool,
+    pub ambient: bool,
+    /// Inner size of a normal window; fullscreen when absent.
+    pub windowed: Option<(u32, u32)>,
+    /// On-screen size of a cell in pixels at the starting zoom.
+    pub cell_size: f32,
+    /// Seconds between generations; the mode's default when absent.
+    pub tick_interval: Option<f32>,
+    pub seed: Option<u64>,
+    /// Chance that a cell of the initial soup is alive.
+    pub density: f64,
+    pub rule: Option<Rule>,
+    pub pattern: Option<String>,
+    /// Snapshot file to resume from instead of starting a new universe.
+    pub load: Option<String>,
+    pub boundary: Option<Boundary>,
+    /// Dense for new universes and whichever engine saved a loaded one when absent.
+    pub engine: Option<EngineKind>,
+    pub history: usize,
+    /// Start a new soup after this many generations without change or with only a short cycle.
+    pub auto_reseed: Option<u64>,
+    /// Generations between the frames of a GIF recording.
+    pub gif_every: u32,
+}
+
+impl Options {
+    pub fn parse() -> Options {
+        let default_cell_size = SQUARE_SIZE.to_string();
+        let default_history = HISTORY_LENGTH.to_string();
+        let matches = App::new("gol-nvg")
+            .version(clap::crate_version!())
+            .about("Conway's Game of Life drawn with nanovg")
+            .arg(Arg::with_name("cell-size")
+                .long("cell-size")
+                .value_name("PIXELS")
+                .default_value(&default_cell_size)
+                .validator(cell_size)
+                .help("Size of a cell on screen before zooming"))
+            .arg(Arg::with_name("tick-ms")
+                .long("tick-ms")
+                .value_name("MS")
+                .validator(parses::<u32>)
+                .help("Milliseconds between generations [default: 100, or 1000 with --ambient]"))
+            .arg(Arg::with_name("windowed")
+                .long("windowed")
+                .value_name("WxH")
+                .validator(|value| window_size(&value).map(|_| ()))
+                .conflicts_with("ambient")
+                .help("Open a normal window of this size instead of going fullscreen"))
+            .arg(Arg::with_name("seed")
+                .long("seed")
+                .value_name("SEED")
+                .validator(parses::<u64>)
+                .help("Seed for the initial soup and noise, to reproduce a run"))
+            .arg(Arg::with_name("density")
+                .long("density")
+                .value_name("FRACTION")
+                .default_value("0.5")
+                .validator(density)
+                .help("Fraction of the initial soup that is alive"))
+            .arg(Arg::with_name("rule")
+                .long("rule")
+                .value_name("RULE")
+                .validator(|value| Rule::parse(&value).map(|_| ()).map_err(|err| err.to_string()))
+                .help("Rulestring such as B36/S23; overrides the rule in --pattern"))
+            .arg(Arg::with_name("pattern")
+                .long("pattern")
+                .value_name("FILE")
+                .help("Start from an RLE or plaintext (.cells) pattern instead of a random soup"))
+            .arg(Arg::with_name("load")
+                .long("load")
+                .value_name("FILE")
+                .conflicts_with_all(&["pattern", "seed", "density"])
+                .help("Resume a simulation saved with W"))
+            .arg(Arg::with_name("boundary")
+                .long("boundary")
+                .value_name("EDGES")
+                .possible_values(&["dead", "wrap"])
+                .help("Whether cells past the edge are dead or wrap around [default: dead]"))
+            .arg(Arg::with_name("engine")
+                .long("engine")
+                .value_name("ENGINE")
+                .possible_values(&["dense", "sparse"])
+                .help("Cell storage; sparse runs a much larger universe [default: dense, or the engine --load was saved with]"))
+            .arg(Arg::with_name("history")
+                .long("history")
+                .value_name("GENERATIONS")
+                .default_value(&default_history)
+                .validator(parses::<usize>)
+                .help("Generations kept for rewinding with Backspace"))
+            .arg(Arg::with_name("gif-every")
+                .long("gif-every")
+                .value_name("GENERATIONS")
+                .default_value("1")
+                .validator(positive::<u32>)
+                .help("Record every Nth generation when recording a GIF with G"))
+            .arg(Arg::with_name("auto-reseed")
+                .long("auto-reseed")
+                .value_name("GENERATIONS")
+                .validator(positive::<u64>)
+                .help("Seed a new soup once the universe has died out or repeated itself for this long"))
+            .arg(Arg::with_name("ambient")
+                .long("ambient")
+                .help("Run as a slow, transparent overlay on top of the desktop"))
+            .arg(Arg::with_name("headless")
+                .long("headless")
+                .conflicts_with_all(&["ambient", "windowed"])
+                .help("Draw the universe in the terminal with block characters instead of opening a window"))
+            .arg(Arg::with_name("bench")
+                .long("bench")
+                .value_name("GENERATIONS")
+                .validator(parses::<u64>)
+                .help("Run this many generations as fast as possible, report generations/s and exit"))
+            .arg(Arg::with_name("serial")
+                .long("serial")
+                .help("Step the universe on a single thread instead of the rayon pool"))
+            .get_matches();
+
+        // Everything below was checked by the validators, so the unwraps can't fail.
+        return Options {
+            bench: matches.value_of("bench").map(|n| n.parse().unwrap()),
+            serial: matches.is_present("serial"),
+            headless: matches.is_present("headless"),
+            ambient: matches.is_present("ambient"),
+            windowed: matches.value_of("windowed").map(|size| window_size(size).unwrap()),
+            cell_size: matches.value_of("cell-size").unwrap().parse().unwrap(),
+            tick_interval: matches.value_of("tick-ms").map(|ms| ms.parse::<u32>().unwrap() as f32 / 1000.0),
+            seed: matches.value_of("seed").map(|seed| seed.parse().unwrap()),
+            density: matches.value_of("density").unwrap().parse().unwrap(),
+            rule: matches.value_of("rule").map(|rule| Rule::parse(rule).unwrap()),
+            pattern: matches.value_of("pattern").map(str::to_string),
+            load: matches.value_of("load").map(str::to_string),
+            boundary: matches.value_of("boundary").map(|boundary| Boundary::parse(boundary).unwrap()),
+            engine: matches.value_of("engine").map(|engine| EngineKind::parse(engine).unwrap()),
+            history: matches.value_of("history").unwrap().parse().unwrap(),
+            auto_reseed: matches.value_of("auto-reseed").map(|n| n.parse().unwrap()),
+            gif_every: matches.value_of("gif-every").unwrap().parse().unwrap(),
+        };
+    }
+}
+
+fn parses<T: FromStr>(value: String) -> Result<(), String> {
+    match value.parse::<T>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err(format!("{:?} is not a valid number", value)),
+    }
+}
+
+/// Counts of generations where 0 would make no sense, such as recording every 0th one.
+fn positive<T: FromStr + Default + PartialOrd>(value: String) -> Result<(), String> {
+    match value.parse::<T>() {
+        Ok(n) if n > T::default() => Ok(()),
+        _ => Err(format!("expected a positive number of generations, got {:?}", value)),
+    }
+}
+
+/// Cell sizes the camera's zoom limits can show.
+fn cell_size(value: String) -> Result<(), String> {
+    let (min, max) = (SQUARE_SIZE as f32 * MIN_ZOOM, SQUARE_SIZE as f32 * MAX_ZOOM);
+    match value.parse::<f32>() {
+        Ok(size) if size >= min && size <= max => Ok(()),
+        _ => Err(format!("cell size must be between {} and {} pixels", min, max)),
+    }
+}
+
+fn density(value: String) -> Result<(), String> {
+    match value.parse::<f64>() {
+        Ok(density) if density >= 0.0 && density <= 1.0 => Ok(()),
+        _ => Err(format!("density must be between 0 and 1, got {:?}", value)),
+    }
+}
+
+fn window_size(value: &str) -> Result<(u32, u32), String> {
+    let mut parts = value.splitn(2, |c| c == 'x' || c == 'X').map(str::parse::<u32>);
+    match (parts.next(), parts.next()) {
+        (Some(Ok(width)), Some(Ok(height))) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(format!("window size must look like 1280x720, got {:?}", value)),
+    }
+}
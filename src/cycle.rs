@@ -0,0 +1,87 @@
+use crate::Universe;
+use std::collections::VecDeque;
+use std::fmt;
+
+/// Generations whose hashes are remembered, i.e. the longest period that can be detected.
+const HASH_HISTORY: usize = 32;
+
+/// What the universe has settled into, judged from its recent generations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Activity {
+    /// No repeat among the recent generations.
+    Active,
+    /// Every cell is dead.
+    Extinct,
+    /// The last generation left the grid unchanged.
+    Static,
+    /// The grid repeats itself every `period` generations.
+    Oscillating(usize),
+}
+
+impl fmt::Display for Activity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Activity::Active => write!(f, "active"),
+            Activity::Extinct => write!(f, "extinct"),
+            Activity::Static => write!(f, "static"),
+            Activity::Oscillating(period) => write!(f, "oscillating (period {})", period),
+        }
+    }
+}
+
+/// Spots extinction and short cycles by comparing each generation's hash with the ones before.
+///
+/// Hashes cover cell positions, so gliders and other spaceships keep the universe `Active`
+/// until they crash into something.
+pub struct CycleDetector {
+    hashes: VecDeque<u64>,
+    pub activity: Activity,
+    /// Consecutive generations that were not `Active`.
+    pub stagnant_for: u64,
+}
+
+impl CycleDetector {
+    pub fn new() -> CycleDetector {
+        return CycleDetector {
+            hashes: VecDeque::with_capacity(HASH_HISTORY),
+            activity: Activity::Active,
+            stagnant_for: 0,
+        };
+    }
+
+    /// Forgets the past, e.g. after the grid was rewound or replaced.
+    pub fn reset(&mut self) {
+        self.hashes.clear();
+        self.activity = Activity::Active;
+        self.stagnant_for = 0;
+    }
+
+    /// Classifies the generation the universe just reached; call after every tick.
+    pub fn record(&mut self, universe: &Universe) {
+        let hash = universe.engine.hash();
+        self.activity = if universe.stats.population == 0 {
+            Activity::Extinct
+        } else {
+            match self.hashes.iter().rev().position(|&h| h == hash) {
+                Some(0) => Activity::Static,
+                Some(i) => Activity::Oscillating(i + 1),
+                None => Activity::Active,
+            }
+        };
+        self.stagnant_for = match self.activity {
+            Activity::Active => 0,
+            _ => self.stagnant_for + 1,
+        };
+
+        if self.hashes.len() == HASH_HISTORY {
+            self.hashes.pop_front();
+        }
+        self.hashes.push_back(hash);
+    }
+}
+
+impl Default for CycleDetector {
+    fn default() -> CycleDetector {
+        return CycleDetector::new();
+    }
+}
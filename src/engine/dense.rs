@@ -0,0 +1,164 @@
+use super::{neighborhood, Engine, EngineKind, NextState, NEVER_ALIVE};
+use crate::{Boundary, Cell};
+use rayon::prelude::*;
+use std::ops::Range;
+
+/// Largest grid the dense engine allocates. Each cell takes four bytes across the two buffers
+/// and the ages, so this is 1 GiB.
+const MAX_DENSE_CELLS: usize = 1 << 28;
+
+/// Every cell of the grid in one row-major `Vec`, double buffered.
+pub struct DenseEngine {
+    width: u32,
+    height: u32,
+    cells: Vec<Cell>,
+    /// Back buffer that `step` writes the next generation into before swapping it with `cells`.
+    next: Vec<Cell>,
+    /// Generations each cell has spent in its current state, so live cells count how long they
+    /// have been alive and dead cells how long ago they died.
+    ages: Vec<u16>,
+}
+
+impl DenseEngine {
+    pub fn new(width: u32, height: u32) -> anyhow::Result<DenseEngine> {
+        let len = cell_count(width, height)?;
+        return Ok(DenseEngine {
+            width,
+            height,
+            cells: vec![Cell::Dead; len],
+            next: Vec::new(),
+            ages: vec![NEVER_ALIVE; len],
+        });
+    }
+
+    fn get_index(&self, row: u32, column: u32) -> usize {
+        row as usize * self.width as usize + column as usize
+    }
+
+    fn live_neighbors(&self, row: u32, column: u32, boundary: Boundary) -> u8 {
+        let mut count = 0;
+        let cols = neighborhood(column, self.width, boundary);
+        for neighbor_row in neighborhood(row, self.height, boundary).iter().flatten() {
+            let row_start = *neighbor_row as usize * self.width as usize;
+            for neighbor_col in cols.iter().flatten() {
+                count += self.cells[row_start + *neighbor_col as usize] as u8;
+            }
+        }
+        // The loops above counted the cell itself.
+        return count - self.get(row, column) as u8;
+    }
+
+    fn step_row(&self, row: u32, out: &mut [Cell], boundary: Boundary, next_state: NextState) {
+        for (col, cell) in out.iter_mut().enumerate() {
+            let col = col as u32;
+            *cell = next_state(self.get(row, col), self.live_neighbors(row, col, boundary), col);
+        }
+    }
+}
+
+impl Engine for DenseEngine {
+    fn kind(&self) -> EngineKind {
+        return EngineKind::Dense;
+    }
+
+    fn get(&self, row: u32, col: u32) -> Cell {
+        return self.cells[self.get_index(row, col)];
+    }
+
+    fn age(&self, row: u32, col: u32) -> u16 {
+        return self.ages[self.get_index(row, col)];
+    }
+
+    fn set(&mut self, row: u32, col: u32, cell: Cell) {
+        let idx = self.get_index(row, col);
+        if self.cells[idx] != cell {
+            self.cells[idx] = cell;
+            self.ages[idx] = 0;
+        }
+    }
+
+    fn resize(&mut self, width: u32, height: u32) -> anyhow::Result<()> {
+        let len = cell_count(width, height)?;
+        let mut cells = vec![Cell::Dead; len];
+        let mut ages = vec![NEVER_ALIVE; len];
+        for row in 0..height.min(self.height) {
+            for col in 0..width.min(self.width) {
+                let idx = row as usize * width as usize + col as usize;
+                cells[idx] = self.get(row, col);
+                ages[idx] = self.age(row, col);
+            }
+        }
+        self.width = width;
+        self.height = height;
+        self.cells = cells;
+        self.ages = ages;
+        return Ok(());
+    }
+
+    fn clear(&mut self) {
+        self.cells = vec![Cell::Dead; self.cells.len()];
+        self.ages = vec![NEVER_ALIVE; self.ages.len()];
+    }
+
+    fn population(&self) -> u32 {
+        return self.cells.par_iter().filter(|&&cell| cell == Cell::Alive).count() as u32;
+    }
+
+    fn live_cells(&self) -> Vec<(u32, u32)> {
+        let width = self.width as usize;
+        return self.cells.iter().enumerate()
+            .filter(|&(_, &cell)| cell == Cell::Alive)
+            .map(|(idx, _)| ((idx / width) as u32, (idx % width) as u32))
+            .collect();
+    }
+
+    fn visit(&self, rows: Range<u32>, cols: Range<u32>, f: &mut dyn FnMut(u32, u32, Cell, u16)) {
+        for row in rows {
+            for col in cols.clone() {
+                let idx = self.get_index(row, col);
+                if self.cells[idx] == Cell::Alive || self.ages[idx] != NEVER_ALIVE {
+                    f(row, col, self.cells[idx], self.ages[idx]);
+                }
+            }
+        }
+    }
+
+    fn step(&mut self, boundary: Boundary, parallel: bool, next_state: NextState) -> Vec<(u32, u32)> {
+        // Step into the back buffer, then swap it in, so no grid is allocated per tick.
+        let mut next = std::mem::take(&mut self.next);
+        next.resize(self.cells.len(), Cell::Dead);
+
+        let width = self.width as usize;
+        if parallel {
+            let engine: &DenseEngine = self;
+            next.par_chunks_mut(width).enumerate().for_each(|(row, out)| engine.step_row(row as u32, out, boundary, next_state));
+        } else {
+            for (row, out) in next.chunks_mut(width).enumerate() {
+                self.step_row(row as u32, out, boundary, next_state);
+            }
+        }
+
+        self.ages.par_iter_mut()
+            .zip(self.cells.par_iter().zip(next.par_iter()))
+            .for_each(|(age, (&cell, &next_cell))| {
+                *age = if next_cell == cell { age.saturating_add(1) } else { 0 };
+            });
+        let cells = &self.cells;
+        let changed = (0..next.len()).into_par_iter()
+            .filter(|&idx| next[idx] != cells[idx])
+            .map(|idx| ((idx / width) as u32, (idx % width) as u32))
+            .collect();
+
+        self.next = std::mem::replace(&mut self.cells, next);
+        return changed;
+    }
+}
+
+/// Cells in a `width` x `height` grid, or an error if that is more than the dense engine
+/// will allocate.
+fn cell_count(width: u32, height: u32) -> anyhow::Result<usize> {
+    match (width as usize).checked_mul(height as usize) {
+        Some(len) if len <= MAX_DENSE_CELLS => Ok(len),
+        _ => anyhow::bail!("a {}x{} grid is too large for the dense engine; try --engine sparse", width, height),
+    }
+}
@@ -0,0 +1,167 @@
+mod dense;
+mod sparse;
+
+pub use dense::DenseEngine;
+pub use sparse::SparseEngine;
+
+use crate::rule::Rule;
+use crate::{Boundary, Cell};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Marks dead cells that have not been alive since the universe was seeded.
+pub const NEVER_ALIVE: u16 = u16::MAX;
+
+/// The rule `Engine::step` applies: maps a cell, its live neighbour count and its column to the
+/// cell's state in the next generation.
+pub type NextState<'a> = &'a (dyn Fn(Cell, u8, u32) -> Cell + Sync);
+
+/// Storage and stepping for the cells of a universe. Rules, noise and statistics live in
+/// `Universe`, which drives whichever engine was picked at startup.
+pub trait Engine: Send + Sync {
+    /// Which engine this is, so a saved universe comes back in the same one.
+    fn kind(&self) -> EngineKind;
+
+    fn get(&self, row: u32, col: u32) -> Cell;
+
+    /// Generations the cell has spent in its current state, or `NEVER_ALIVE`.
+    fn age(&self, row: u32, col: u32) -> u16;
+
+    /// Changes a cell's state, restarting its age. Does nothing if the state is unchanged.
+    fn set(&mut self, row: u32, col: u32, cell: Cell);
+
+    /// Crops or extends the grid, keeping the cells in the overlapping region. Fails, leaving
+    /// the grid as it was, if the engine can't hold the new size.
+    fn resize(&mut self, width: u32, height: u32) -> anyhow::Result<()>;
+
+    fn clear(&mut self);
+
+    fn population(&self) -> u32;
+
+    /// Every live cell as (row, column).
+    fn live_cells(&self) -> Vec<(u32, u32)>;
+
+    /// A hash of which cells are alive that doesn't depend on the order they are stored in.
+    fn hash(&self) -> u64 {
+        return self.live_cells().iter().map(|&(row, col)| mix((row as u64) << 32 | col as u64)).fold(0, u64::wrapping_add);
+    }
+
+    /// Calls `f(row, col, cell, age)` for the cells in the given region that are alive or have
+    /// been alive at some point, so the viewer never has to walk empty space cell by cell.
+    fn visit(&self, rows: Range<u32>, cols: Range<u32>, f: &mut dyn FnMut(u32, u32, Cell, u16));
+
+    /// Advances one generation and returns the cells whose state changed.
+    fn step(&mut self, boundary: Boundary, parallel: bool, next_state: NextState) -> Vec<(u32, u32)>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EngineKind {
+    /// A flat `Vec<Cell>` covering the whole grid, stepped in parallel.
+    Dense,
+    /// Only live cells are stored, so mostly empty universes can be enormous.
+    Sparse,
+}
+
+impl EngineKind {
+    pub fn parse(name: &str) -> anyhow::Result<EngineKind> {
+        match name {
+            "dense" => Ok(EngineKind::Dense),
+            "sparse" => Ok(EngineKind::Sparse),
+            _ => anyhow::bail!("unknown engine {:?}, expected dense or sparse", name),
+        }
+    }
+
+    /// Creates an empty grid, failing if it is too large for this kind of engine.
+    pub fn create(self, width: u32, height: u32) -> anyhow::Result<Box<dyn Engine>> {
+        match self {
+            EngineKind::Dense => Ok(Box::new(DenseEngine::new(width, height)?)),
+            EngineKind::Sparse => Ok(Box::new(SparseEngine::new(width, height))),
+        }
+    }
+
+    /// Fails for rules this kind of engine can't run the way the others would.
+    pub fn check_rule(self, rule: &Rule) -> anyhow::Result<()> {
+        if self == EngineKind::Sparse && rule.births_from_nothing() {
+            anyhow::bail!("the sparse engine only visits cells next to live ones and can't run B0 rules such as {}; try --engine dense", rule);
+        }
+        return Ok(());
+    }
+}
+
+impl Default for EngineKind {
+    fn default() -> EngineKind {
+        return EngineKind::Dense;
+    }
+}
+
+/// The indices before, at and after `v` along an axis of length `len`, with `None` for
+/// positions that fall off a dead edge.
+fn neighborhood(v: u32, len: u32, boundary: Boundary) -> [Option<u32>; 3] {
+    let wrap = boundary == Boundary::Wrap;
+    let before = if v > 0 { Some(v - 1) } else if wrap { Some(len - 1) } else { None };
+    let after = if v + 1 < len { Some(v + 1) } else if wrap { Some(0) } else { None };
+    return [before, Some(v), after];
+}
+
+/// The splitmix64 finalizer: scrambles a cell position so that summing them makes a good hash.
+fn mix(v: u64) -> u64 {
+    let v = (v ^ (v >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    let v = (v ^ (v >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    return v ^ (v >> 31);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule;
+    use crate::Universe;
+
+    fn soup(engine: EngineKind, rulestring: &str, boundary: Boundary) -> Universe {
+        let mut universe = Universe::new(48, 40, engine).unwrap();
+        universe.set_rule(Rule::parse(rulestring).unwrap()).unwrap();
+        universe.boundary = boundary;
+        universe.randomize(42, 0.35);
+        return universe;
+    }
+
+    /// Live cells with their ages, in row-major order. Dead cells are left out because the
+    /// sparse engine forgets their ages after a while and the dense one doesn't.
+    fn live_with_ages(universe: &Universe) -> Vec<(u32, u32, u16)> {
+        let mut cells = Vec::new();
+        universe.engine.visit(0..universe.height, 0..universe.width, &mut |row, col, cell, age| {
+            if cell == Cell::Alive {
+                cells.push((row, col, age));
+            }
+        });
+        cells.sort();
+        return cells;
+    }
+
+    #[test]
+    fn dense_and_sparse_agree() {
+        for rulestring in &rule::PRESETS {
+            for &boundary in &[Boundary::Dead, Boundary::Wrap] {
+                let mut dense = soup(EngineKind::Dense, rulestring, boundary);
+                let mut sparse = soup(EngineKind::Sparse, rulestring, boundary);
+                for generation in 1..=40 {
+                    dense.tick();
+                    sparse.tick();
+                    let context = format!("{} with {:?} edges, generation {}", rulestring, boundary, generation);
+                    assert_eq!(dense.stats.population, sparse.stats.population, "{}", context);
+                    assert_eq!(dense.engine.hash(), sparse.engine.hash(), "{}", context);
+                }
+                assert_eq!(live_with_ages(&dense), live_with_ages(&sparse), "{} with {:?} edges", rulestring, boundary);
+            }
+        }
+    }
+    #[test]
+    fn sparse_refuses_b0_rules() {
+        let seeds_with_b0 = Rule::parse("B012/S").unwrap();
+        let mut sparse = Universe::new(16, 16, EngineKind::Sparse).unwrap();
+        assert!(sparse.set_rule(seeds_with_b0).is_err());
+        assert_eq!(sparse.rule, Rule::conway());
+        let mut dense = Universe::new(16, 16, EngineKind::Dense).unwrap();
+        assert!(dense.set_rule(seeds_with_b0).is_ok());
+    }
+}
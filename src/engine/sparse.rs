@@ -0,0 +1,288 @@
+use super::{neighborhood, Engine, EngineKind, NextState, NEVER_ALIVE};
+use crate::{Boundary, Cell};
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// How many generations a dead cell's age is remembered for, long enough for the themes' fading
+/// trails. Older dead cells report `NEVER_ALIVE`.
+const DEAD_MEMORY: u16 = 32;
+
+/// Stores only live (and recently dead) cells, so memory and stepping cost follow the population
+/// rather than the size of the grid. Cells are kept ordered by row and then column, so the
+/// viewer's region is a handful of range queries rather than a scan of every cell. The same
+/// order lets a step count neighbours by sweeping the sorted columns of three rows at a time
+/// instead of hashing positions, one row per task on the rayon pool.
+///
+/// Births with zero live neighbours would mean visiting every empty cell, so B0 rules are
+/// refused by `EngineKind::check_rule`.
+pub struct SparseEngine {
+    width: u32,
+    height: u32,
+    /// Live cells and how many generations they have been alive.
+    live: BTreeMap<(u32, u32), u16>,
+    /// Recently dead cells and the step they died in. Cells older than `DEAD_MEMORY` are only
+    /// swept out every so often, so readers have to skip them.
+    dead: BTreeMap<(u32, u32), u64>,
+    /// Steps taken so far, the clock that `dead` is measured against.
+    steps: u64,
+    /// Step at which forgotten cells are next swept out of `dead`.
+    next_sweep: u64,
+}
+
+/// What a step does to a cell that is alive or has live neighbours.
+enum Fate {
+    Survives(u16),
+    Born,
+    Dies,
+}
+
+impl SparseEngine {
+    pub fn new(width: u32, height: u32) -> SparseEngine {
+        return SparseEngine { width, height, live: BTreeMap::new(), dead: BTreeMap::new(), steps: 0, next_sweep: 0 };
+    }
+
+    /// Generations since a cell died at step `died`, or `None` once that is no longer tracked.
+    fn dead_age(&self, died: u64) -> Option<u16> {
+        let age = self.steps - died;
+        if age < DEAD_MEMORY as u64 {
+            Some(age as u16)
+        } else {
+            None
+        }
+    }
+}
+
+impl Engine for SparseEngine {
+    fn kind(&self) -> EngineKind {
+        return EngineKind::Sparse;
+    }
+
+    fn get(&self, row: u32, col: u32) -> Cell {
+        if self.live.contains_key(&(row, col)) {
+            Cell::Alive
+        } else {
+            Cell::Dead
+        }
+    }
+
+    fn age(&self, row: u32, col: u32) -> u16 {
+        let pos = (row, col);
+        if let Some(&age) = self.live.get(&pos) {
+            return age;
+        }
+        return self.dead.get(&pos).and_then(|&died| self.dead_age(died)).unwrap_or(NEVER_ALIVE);
+    }
+
+    fn set(&mut self, row: u32, col: u32, cell: Cell) {
+        let pos = (row, col);
+        match cell {
+            Cell::Alive if !self.live.contains_key(&pos) => {
+                self.dead.remove(&pos);
+                self.live.insert(pos, 0);
+            }
+            Cell::Dead if self.live.remove(&pos).is_some() => {
+                self.dead.insert(pos, self.steps);
+            }
+            _ => (),
+        }
+    }
+
+    fn resize(&mut self, width: u32, height: u32) -> anyhow::Result<()> {
+        self.width = width;
+        self.height = height;
+        self.live.retain(|&(row, col), _| row < height && col < width);
+        self.dead.retain(|&(row, col), _| row < height && col < width);
+        return Ok(());
+    }
+
+    fn clear(&mut self) {
+        self.live.clear();
+        self.dead.clear();
+    }
+
+    fn population(&self) -> u32 {
+        return self.live.len() as u32;
+    }
+
+    fn live_cells(&self) -> Vec<(u32, u32)> {
+        return self.live.keys().cloned().collect();
+    }
+
+    fn visit(&self, rows: Range<u32>, cols: Range<u32>, f: &mut dyn FnMut(u32, u32, Cell, u16)) {
+        visit_region(&self.live, &rows, &cols, &mut |row, col, &age| f(row, col, Cell::Alive, age));
+        visit_region(&self.dead, &rows, &cols, &mut |row, col, &died| {
+            if let Some(age) = self.dead_age(died) {
+                f(row, col, Cell::Dead, age);
+            }
+        });
+    }
+
+    fn step(&mut self, boundary: Boundary, parallel: bool, next_state: NextState) -> Vec<(u32, u32)> {
+        self.steps += 1;
+        // The live cells in row-major order, with where each occupied row starts and ends.
+        let live: Vec<((u32, u32), u16)> = self.live.iter().map(|(&pos, &age)| (pos, age)).collect();
+        let mut rows: Vec<(u32, Range<usize>)> = Vec::new();
+        for (i, &((row, _), _)) in live.iter().enumerate() {
+            match rows.last_mut() {
+                Some((last, cells)) if *last == row => cells.end = i + 1,
+                _ => rows.push((row, i..i + 1)),
+            }
+        }
+        // Only rows next to an occupied one can change.
+        let mut targets = Vec::with_capacity(rows.len() * 3);
+        for &(row, _) in &rows {
+            targets.extend(neighborhood(row, self.height, boundary).iter().flatten());
+        }
+        targets.sort_unstable();
+        targets.dedup();
+
+        let band = Band { live: &live, rows: &rows, width: self.width, height: self.height, boundary, next_state };
+        let fates: Vec<((u32, u32), Fate)> = if parallel {
+            targets.par_iter()
+                .map_init(Vec::new, |sources, &row| {
+                    let mut fates = Vec::new();
+                    band.step_row(row, sources, &mut fates);
+                    fates
+                })
+                .flat_map_iter(|fates| fates)
+                .collect()
+        } else {
+            let (mut sources, mut fates) = (Vec::new(), Vec::new());
+            for &row in &targets {
+                band.step_row(row, &mut sources, &mut fates);
+            }
+            fates
+        };
+
+        let mut next_live = Vec::with_capacity(live.len());
+        let mut changed = Vec::new();
+        for (pos, fate) in fates {
+            match fate {
+                Fate::Survives(age) => next_live.push((pos, age)),
+                Fate::Born => {
+                    next_live.push((pos, 0));
+                    self.dead.remove(&pos);
+                    changed.push(pos);
+                }
+                Fate::Dies => {
+                    self.dead.insert(pos, self.steps);
+                    changed.push(pos);
+                }
+            }
+        }
+        // Already in order, so this builds the tree without searching it.
+        self.live = next_live.into_iter().collect();
+
+        // Forgotten dead cells are already hidden, so sweeping them out now and then is enough.
+        if self.steps >= self.next_sweep {
+            let steps = self.steps;
+            self.dead.retain(|_, &mut died| steps - died < DEAD_MEMORY as u64);
+            self.next_sweep = steps + DEAD_MEMORY as u64;
+        }
+        return changed;
+    }
+}
+
+/// What `SparseEngine::step` needs to work out the next generation of any one row, so rows can
+/// be stepped on separate threads.
+struct Band<'a> {
+    /// Live cells in row-major order.
+    live: &'a [((u32, u32), u16)],
+    /// Each occupied row and the part of `live` it takes up, in order.
+    rows: &'a [(u32, Range<usize>)],
+    width: u32,
+    height: u32,
+    boundary: Boundary,
+    next_state: NextState<'a>,
+}
+
+impl<'a> Band<'a> {
+    fn cells_in_row(&self, row: u32) -> &'a [((u32, u32), u16)] {
+        match self.rows.binary_search_by_key(&row, |&(row, _)| row) {
+            Ok(i) => &self.live[self.rows[i].1.clone()],
+            Err(_) => &[],
+        }
+    }
+
+    /// Appends the fate of every cell in `row` that is alive or has a live neighbour, in
+    /// column order. `sources` is scratch space that callers can reuse between rows.
+    fn step_row(&self, row: u32, sources: &mut Vec<i64>, fates: &mut Vec<((u32, u32), Fate)>) {
+        let width = self.width as i64;
+        let wrap = self.boundary == Boundary::Wrap;
+
+        // Columns of the live cells in this row and the two beside it, each as many times as
+        // `neighborhood` lists its row. On a wrapping grid, cells in the edge columns also
+        // appear just past the opposite edge, so every count below is a plain window.
+        sources.clear();
+        for &source_row in neighborhood(row, self.height, self.boundary).iter().flatten() {
+            for &((_, col), _) in self.cells_in_row(source_row) {
+                let col = col as i64;
+                sources.push(col);
+                if wrap && col == 0 {
+                    sources.push(width);
+                }
+                if wrap && col == width - 1 {
+                    sources.push(-1);
+                }
+            }
+        }
+        // A few sorted runs, which a stable sort merges in linear time.
+        sources.sort();
+
+        let own = self.cells_in_row(row);
+        let (mut next_own, mut low, mut high) = (0, 0, 0);
+        let mut last = -2;
+        for &source in sources.iter() {
+            // Each candidate is visited once, in order, so all three cursors only move forward.
+            for col in (source - 1).max(last + 1)..=source + 1 {
+                if col < 0 || col >= width {
+                    continue;
+                }
+                last = col;
+                while sources[low] < col - 1 {
+                    low += 1;
+                }
+                while high < sources.len() && sources[high] <= col + 1 {
+                    high += 1;
+                }
+                while next_own < own.len() && ((own[next_own].0).1 as i64) < col {
+                    next_own += 1;
+                }
+                let age = match own.get(next_own) {
+                    Some(&((_, own_col), age)) if own_col as i64 == col => Some(age),
+                    _ => None,
+                };
+                // The window also counted the cell itself when it is alive.
+                let live_neighbors = (high - low - age.is_some() as usize) as u8;
+                let pos = (row, col as u32);
+                let fate = match age {
+                    Some(age) if (self.next_state)(Cell::Alive, live_neighbors, pos.1) == Cell::Alive => Fate::Survives(age.saturating_add(1)),
+                    Some(_) => Fate::Dies,
+                    None if (self.next_state)(Cell::Dead, live_neighbors, pos.1) == Cell::Alive => Fate::Born,
+                    None => continue,
+                };
+                fates.push((pos, fate));
+            }
+        }
+    }
+}
+
+/// Calls `f` for the cells of `cells` inside the region, jumping from one occupied row to the
+/// next so empty rows and the columns outside the region are never walked.
+fn visit_region<V>(cells: &BTreeMap<(u32, u32), V>, rows: &Range<u32>, cols: &Range<u32>, f: &mut dyn FnMut(u32, u32, &V)) {
+    if rows.start >= rows.end || cols.start >= cols.end {
+        return;
+    }
+    let mut row = rows.start;
+    while row < rows.end {
+        let next_row = match cells.range((row, cols.start)..(rows.end, 0)).next() {
+            Some((&(next_row, _), _)) => next_row,
+            None => return,
+        };
+        for (&(row, col), value) in cells.range((next_row, cols.start)..(next_row, cols.end)) {
+            f(row, col, value);
+        }
+        row = next_row + 1;
+    }
+}
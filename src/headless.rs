@@ -0,0 +1,87 @@
+use crate::cli::Options;
+use crate::{auto_reseed, TICK_INTERVAL};
+use gol_nvg::cycle::CycleDetector;
+use gol_nvg::Universe;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+/// Terminal size assumed when it can't be queried and `COLUMNS` and `LINES` are not set.
+const DEFAULT_COLUMNS: u32 = 80;
+const DEFAULT_LINES: u32 = 24;
+
+/// Advances the universe `generations` times as fast as possible and reports the rate.
+pub fn bench(universe: &mut Universe, generations: u64) {
+    let start = Instant::now();
+    for _ in 0..generations {
+        universe.tick();
+    }
+    let elapsed = start.elapsed().as_secs_f32();
+    let threads = if universe.parallel { format!("{} threads", rayon::current_num_threads()) } else { "serial".to_string() };
+    println!(
+        "{} generations of {}x{} in {:.3}s ({:.1} generations/s, {})",
+        generations,
+        universe.width,
+        universe.height,
+        elapsed,
+        generations as f32 / elapsed,
+        threads,
+    );
+}
+
+/// Runs the simulation in the terminal until interrupted, redrawing the middle of the universe
+/// after every generation.
+pub fn run(mut universe: Universe, options: &Options) {
+    let interval = Duration::from_secs_f32(options.tick_interval.unwrap_or(TICK_INTERVAL));
+    let (columns, lines) = terminal_size();
+    let columns = columns.min(universe.width);
+    // Each line of text shows two rows; the last line is kept for the status.
+    let rows = ((lines - 1) * 2).min(universe.height);
+    let (top, left) = ((universe.height - rows) / 2, (universe.width - columns) / 2);
+    let mut detector = CycleDetector::new();
+
+    loop {
+        let frame = universe.to_block_text(top..top + rows, left..left + columns);
+        let stats = &universe.stats;
+        let mut stdout = std::io::stdout();
+        // Move to the top left and clear the screen before each frame.
+        write!(
+            stdout,
+            "\x1b[H\x1b[2J{}Generation: {}  Population: {}  State: {}",
+            frame, stats.generation, stats.population, detector.activity,
+        ).unwrap();
+        stdout.flush().unwrap();
+
+        std::thread::sleep(interval);
+        universe.tick();
+        detector.record(&universe);
+        auto_reseed(&mut universe, &mut detector, options);
+    }
+}
+
+/// Columns and lines of the terminal, asked of the terminal itself where possible since shells
+/// rarely export `COLUMNS` and `LINES` to child processes.
+fn terminal_size() -> (u32, u32) {
+    return query_terminal_size().unwrap_or_else(|| {
+        (terminal_dimension("COLUMNS", DEFAULT_COLUMNS), terminal_dimension("LINES", DEFAULT_LINES))
+    });
+}
+
+#[cfg(unix)]
+fn query_terminal_size() -> Option<(u32, u32)> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    // Fails when stdout is not a terminal, e.g. when it is piped into a file.
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } != 0 {
+        return None;
+    }
+    return Some((size.ws_col as u32, size.ws_row as u32)).filter(|&(columns, lines)| columns > 1 && lines > 1);
+}
+
+#[cfg(not(unix))]
+fn query_terminal_size() -> Option<(u32, u32)> {
+    return None;
+}
+
+/// Reads a terminal dimension that shells export, such as `COLUMNS`.
+fn terminal_dimension(name: &str, default: u32) -> u32 {
+    return std::env::var(name).ok().and_then(|value| value.parse().ok()).filter(|&n| n > 1).unwrap_or(default);
+}
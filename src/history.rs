@@ -0,0 +1,185 @@
+use crate::Universe;
+use std::collections::VecDeque;
+
+/// A past generation's live cells, packed one bit per cell of their bounding box or listed by
+/// position, whichever takes less memory.
+struct Snapshot {
+    width: u32,
+    height: u32,
+    cells: Packed,
+}
+
+enum Packed {
+    /// The `rows` x `columns` box whose top left cell is (`top`, `left`), in row-major order.
+    Bits { top: u32, left: u32, rows: u32, columns: u32, bits: Vec<u64> },
+    List(Vec<(u32, u32)>),
+}
+
+impl Snapshot {
+    fn capture(universe: &Universe) -> Snapshot {
+        let live = universe.engine.live_cells();
+        let top = live.iter().map(|&(row, _)| row).min().unwrap_or(0);
+        let left = live.iter().map(|&(_, col)| col).min().unwrap_or(0);
+        let rows = live.iter().map(|&(row, _)| row + 1).max().unwrap_or(0) - top;
+        let columns = live.iter().map(|&(_, col)| col + 1).max().unwrap_or(0) - left;
+        let area = rows as u64 * columns as u64;
+        // A listed cell takes 64 bits, so the bitset wins once more than one cell in 64 of the
+        // box is alive.
+        let cells = if (live.len() as u64) < area / 64 {
+            Packed::List(live)
+        } else {
+            let mut bits = vec![0u64; area.div_ceil(64) as usize];
+            for (row, col) in live {
+                let idx = (row - top) as usize * columns as usize + (col - left) as usize;
+                bits[idx / 64] |= 1 << (idx % 64);
+            }
+            Packed::Bits { top, left, rows, columns, bits }
+        };
+        return Snapshot { width: universe.width, height: universe.height, cells };
+    }
+
+    fn live_cells(self) -> Vec<(u32, u32)> {
+        match self.cells {
+            Packed::List(live) => live,
+            Packed::Bits { top, left, rows, columns, bits } => {
+                let columns = columns as usize;
+                (0..rows as usize * columns)
+                    .filter(|&idx| bits[idx / 64] & (1 << (idx % 64)) != 0)
+                    .map(|idx| (top + (idx / columns) as u32, left + (idx % columns) as u32))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Ring buffer of the generations that came before the current one, newest last.
+pub struct History {
+    capacity: usize,
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> History {
+        return History { capacity, snapshots: VecDeque::with_capacity(capacity) };
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    /// Forgets every recorded generation, e.g. once the universe was replaced with a new soup.
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+
+    /// Remembers the universe as it is now; call right before ticking it.
+    pub fn record(&mut self, universe: &Universe) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(Snapshot::capture(universe));
+    }
+
+    /// Steps the universe back one generation. Returns false when there is nothing to go back
+    /// to. Ticking again afterwards resumes forward from the rewound state.
+    pub fn rewind(&mut self, universe: &mut Universe) -> bool {
+        match self.snapshots.pop_back() {
+            Some(snapshot) if snapshot.width == universe.width && snapshot.height == universe.height => {
+                universe.rewind_to(snapshot.live_cells());
+                true
+            }
+            Some(_) => {
+                // The grid was resized since this was recorded, so none of it lines up any more.
+                self.snapshots.clear();
+                false
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::EngineKind;
+    use crate::pattern::{Pattern, LIBRARY};
+
+    fn live(universe: &Universe) -> Vec<(u32, u32)> {
+        let mut cells = universe.engine.live_cells();
+        cells.sort();
+        return cells;
+    }
+
+    /// Ticks `generations` times while recording, then checks that rewinding retraces them.
+    fn rewinds(mut universe: Universe, generations: usize) {
+        let mut history = History::new(generations);
+        let mut past = Vec::new();
+        for _ in 0..generations {
+            past.push((live(&universe), universe.stats.generation));
+            history.record(&universe);
+            universe.tick();
+        }
+        while let Some((cells, generation)) = past.pop() {
+            assert!(history.rewind(&mut universe));
+            assert_eq!(live(&universe), cells);
+            assert_eq!(universe.stats.generation, generation);
+            assert_eq!(universe.stats.population as usize, cells.len());
+        }
+        assert!(history.is_empty());
+        assert!(!history.rewind(&mut universe));
+    }
+
+    #[test]
+    fn rewinds_a_dense_soup() {
+        // Half the cells are alive, so the snapshots are bitsets.
+        let mut universe = Universe::new(64, 48, EngineKind::Dense).unwrap();
+        universe.randomize(7, 0.5);
+        rewinds(universe, 10);
+    }
+
+    #[test]
+    fn rewinds_a_glider_on_a_huge_sparse_grid() {
+        // A glider fills too much of its bounding box for a list, so the snapshots are bitsets
+        // over that box rather than over the billions of cells around it.
+        let mut universe = Universe::new(1 << 20, 1 << 20, EngineKind::Sparse).unwrap();
+        universe.stamp(&Pattern::from_rle(LIBRARY[0]).unwrap(), 1000, 1000);
+        match Snapshot::capture(&universe).cells {
+            Packed::Bits { bits, .. } => assert_eq!(bits.len(), 1),
+            Packed::List(_) => panic!("a lone glider should be packed as bits"),
+        }
+        rewinds(universe, 10);
+    }
+
+    #[test]
+    fn rewinds_gliders_far_apart() {
+        // Two gliders at opposite corners leave their shared bounding box almost empty, so the
+        // snapshots are lists.
+        let mut universe = Universe::new(1 << 20, 1 << 20, EngineKind::Sparse).unwrap();
+        let glider = Pattern::from_rle(LIBRARY[0]).unwrap();
+        universe.stamp(&glider, 10, 10);
+        universe.stamp(&glider, 900_000, 900_000);
+        assert!(matches!(Snapshot::capture(&universe).cells, Packed::List(_)));
+        rewinds(universe, 10);
+    }
+
+    #[test]
+    fn keeps_only_the_most_recent_generations() {
+        let mut universe = Universe::new(32, 32, EngineKind::Dense).unwrap();
+        universe.randomize(3, 0.4);
+        let mut history = History::new(3);
+        for _ in 0..5 {
+            history.record(&universe);
+            universe.tick();
+        }
+        assert_eq!(history.len(), 3);
+        history.clear();
+        assert!(!history.rewind(&mut universe));
+    }
+}
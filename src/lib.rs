@@ -0,0 +1,384 @@
+//! The simulation: universes, their engines and rules, and everything that inspects or stores
+//! them. Nothing in here depends on the window or the GL stack.
+
+pub mod cycle;
+pub mod engine;
+pub mod history;
+pub mod pattern;
+pub mod rule;
+pub mod snapshot;
+pub mod stats;
+
+use engine::{Engine, EngineKind};
+use pattern::Pattern;
+use rule::Rule;
+use serde::{Deserialize, Serialize};
+use stats::Stats;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::HashSet;
+use std::ops::Range;
+
+pub const UNIVERSE_WIDTH: u32 = 1000;
+pub const UNIVERSE_HEIGHT: u32 = 1000;
+pub const SPARSE_UNIVERSE_SIZE: u32 = 100_000;
+/// Most changed cells tracked for the renderer before it has to repaint everything instead.
+/// Without a fixed limit a huge sparse universe that is not being drawn, e.g. while the window
+/// is minimised, would collect changes without bound.
+const MAX_TRACKED_CHANGES: u64 = 1 << 20;
+/// Odd constant (2^64 / golden ratio) spreading generation numbers over the bits of the seed.
+const NOISE_SEED_MIX: u64 = 0x9E37_79B9_7F4A_7C15;
+
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cell {
+    Dead = 0,
+    Alive = 1,
+}
+
+/// How the engines treat cells beyond the edge of the grid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Boundary {
+    /// Everything outside the grid is permanently dead.
+    Dead,
+    /// Edges wrap around, making the grid a torus.
+    Wrap,
+}
+
+impl Boundary {
+    pub fn parse(name: &str) -> anyhow::Result<Boundary> {
+        match name {
+            "dead" => Ok(Boundary::Dead),
+            "wrap" => Ok(Boundary::Wrap),
+            _ => anyhow::bail!("unknown boundary {:?}, expected dead or wrap", name),
+        }
+    }
+}
+
+/// Neighbour count ranges a cell needs to survive or be born, stored as floats
+/// so they can be interpolated between two ends of the grid.
+#[derive(Clone, Copy, Debug)]
+pub struct Thresholds {
+    survive: (f32, f32),
+    birth: (f32, f32),
+}
+
+impl Thresholds {
+    fn lerp(&self, other: &Thresholds, t: f32) -> Thresholds {
+        let mix = |a: f32, b: f32| a + (b - a) * t;
+        return Thresholds {
+            survive: (mix(self.survive.0, other.survive.0), mix(self.survive.1, other.survive.1)),
+            birth: (mix(self.birth.0, other.birth.0), mix(self.birth.1, other.birth.1)),
+        };
+    }
+
+    fn next_state(&self, cell: Cell, live_neighbors: u8) -> Cell {
+        let (min, max) = match cell {
+            Cell::Alive => self.survive,
+            Cell::Dead => self.birth,
+        };
+        let n = live_neighbors as f32;
+        if n >= min.round() && n <= max.round() {
+            Cell::Alive
+        } else {
+            Cell::Dead
+        }
+    }
+}
+
+/// A rule whose thresholds shift smoothly from the left edge of the grid to the right.
+#[derive(Clone, Copy, Debug)]
+pub struct RuleGradient {
+    left: Thresholds,
+    right: Thresholds,
+}
+
+impl RuleGradient {
+    fn thresholds_at(&self, column: u32, width: u32) -> Thresholds {
+        let t = if width > 1 { column as f32 / (width - 1) as f32 } else { 0.0 };
+        return self.left.lerp(&self.right, t);
+    }
+}
+
+impl Default for RuleGradient {
+    fn default() -> RuleGradient {
+        RuleGradient {
+            left: Thresholds { survive: (1.0, 2.0), birth: (3.0, 3.0) },
+            right: Thresholds { survive: (3.0, 6.0), birth: (3.0, 4.0) },
+        }
+    }
+}
+
+pub struct Universe {
+    pub width: u32,
+    pub height: u32,
+    pub engine: Box<dyn Engine>,
+    /// Whether the engine may step rows on the rayon thread pool.
+    pub parallel: bool,
+    pub stats: Stats,
+    /// The whole grid has to be repainted, e.g. after it was cleared or resized.
+    pub dirty: bool,
+    /// Cells that changed since the renderer last caught up; unused while `dirty`.
+    pub changed: Vec<(u32, u32)>,
+    /// Set through `set_rule`, which checks that the engine can run it.
+    pub rule: Rule,
+    pub boundary: Boundary,
+    pub gradient: Option<RuleGradient>,
+    /// Fraction of cells flipped at random after every tick.
+    pub temperature: f32,
+    /// Seed of the initial soup; the noise of each generation is derived from it as well.
+    pub seed: u64,
+}
+
+impl Universe {
+
+    /// Creates an empty universe; see `randomize` for filling it with soup. Fails if the grid
+    /// is too large for the engine.
+    pub fn new(width: u32, height: u32, engine: EngineKind) -> anyhow::Result<Universe> {
+        return Ok(Universe {
+            width,
+            height,
+            engine: engine.create(width, height)?,
+            stats: Stats::new(0),
+            parallel: true,
+            dirty: true,
+            changed: Vec::new(),
+            rule: Rule::conway(),
+            boundary: Boundary::Dead,
+            gradient: None,
+            temperature: 0.0,
+            seed: rand::random(),
+        });
+    }
+
+    /// Replaces the grid with a random soup in which each cell is alive with probability
+    /// `density`. The same seed always gives the same soup, and the same noise afterwards. Huge
+    /// sparse universes only get soup in a `UNIVERSE_WIDTH` x `UNIVERSE_HEIGHT` patch at their
+    /// centre.
+    pub fn randomize(&mut self, seed: u64, density: f64) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        self.clear();
+        let (rows, cols) = self.soup_patch();
+        for row in rows {
+            for col in cols.clone() {
+                if rng.gen_bool(density) {
+                    self.engine.set(row, col, Cell::Alive);
+                }
+            }
+        }
+        self.seed = seed;
+        self.stats = Stats::new(self.engine.population());
+        self.dirty = true;
+    }
+
+    /// Switches to another rule, unless the engine can't run it.
+    pub fn set_rule(&mut self, rule: Rule) -> anyhow::Result<()> {
+        self.engine.kind().check_rule(&rule)?;
+        self.rule = rule;
+        return Ok(());
+    }
+
+    /// Rows and columns of the centred patch that gets soup and noise: the whole grid unless
+    /// it is bigger than `UNIVERSE_WIDTH` x `UNIVERSE_HEIGHT`.
+    fn soup_patch(&self) -> (Range<u32>, Range<u32>) {
+        let (soup_width, soup_height) = (self.width.min(UNIVERSE_WIDTH), self.height.min(UNIVERSE_HEIGHT));
+        let (top, left) = ((self.height - soup_height) / 2, (self.width - soup_width) / 2);
+        return (top..top + soup_height, left..left + soup_width);
+    }
+
+    /// Crops or extends the grid to `width` x `height`, keeping the cells in the overlapping
+    /// region and filling any new area with dead cells. Fails, leaving the universe as it was,
+    /// if the engine can't hold the new size.
+    pub fn resize(&mut self, width: u32, height: u32) -> anyhow::Result<()> {
+        self.engine.resize(width, height)?;
+        self.width = width;
+        self.height = height;
+        self.stats.population = self.engine.population();
+        self.dirty = true;
+        return Ok(());
+    }
+
+    pub fn clear(&mut self) {
+        self.engine.clear();
+        self.stats.population = 0;
+        self.dirty = true;
+    }
+
+    /// Sets the pattern's live cells with its top left corner at (row, column), dropping any
+    /// cells that fall outside the grid.
+    pub fn stamp(&mut self, pattern: &Pattern, row: u32, column: u32) {
+        for &(dr, dc) in &pattern.cells {
+            if row + dr < self.height && column + dc < self.width {
+                self.set_cell(row + dr, column + dc, Cell::Alive);
+            }
+        }
+    }
+
+    /// Replaces the grid with the generation before it, given as its live cells. Only cells
+    /// whose state differs are touched, and those count as having just changed.
+    pub(crate) fn rewind_to(&mut self, live: Vec<(u32, u32)>) {
+        let target: HashSet<(u32, u32)> = live.into_iter().collect();
+        for (row, col) in self.engine.live_cells() {
+            if !target.contains(&(row, col)) {
+                self.engine.set(row, col, Cell::Dead);
+            }
+        }
+        for &(row, col) in &target {
+            self.engine.set(row, col, Cell::Alive);
+        }
+        self.stats.rewind(self.engine.population());
+        self.dirty = true;
+    }
+
+    /// Called once everything in `changed` (or the whole grid, when `dirty`) has been drawn.
+    pub fn mark_drawn(&mut self) {
+        self.dirty = false;
+        self.changed.clear();
+    }
+
+    pub fn get_cell_state(&self, row: u32, column: u32) -> Cell {
+        return self.engine.get(row, column);
+    }
+
+    pub fn get_age(&self, row: u32, column: u32) -> u16 {
+        return self.engine.age(row, column);
+    }
+
+    pub fn set_cell(&mut self, row: u32, column: u32, cell: Cell) {
+        if self.engine.get(row, column) != cell {
+            self.engine.set(row, column, cell);
+            match cell {
+                Cell::Alive => self.stats.population += 1,
+                Cell::Dead => self.stats.population -= 1,
+            }
+            if !self.dirty {
+                self.changed.push((row, column));
+            }
+        }
+    }
+
+    pub fn tick(&mut self) {
+        let (rule, gradient, width) = (self.rule, self.gradient, self.width);
+        let next_state = move |cell: Cell, live_neighbors: u8, col: u32| match gradient {
+            Some(gradient) => gradient.thresholds_at(col, width).next_state(cell, live_neighbors),
+            None => rule.next_state(cell, live_neighbors),
+        };
+        let mut changed = self.engine.step(self.boundary, self.parallel, &next_state);
+
+        let mut births = 0;
+        let mut deaths = 0;
+        for &(row, col) in &changed {
+            match self.engine.get(row, col) {
+                Cell::Alive => births += 1,
+                Cell::Dead => deaths += 1,
+            }
+        }
+
+        // Flip a fixed share of the soup patch at seeded random positions. Keeping to the patch
+        // bounds the work on huge sparse universes, where a share of the whole grid would be
+        // billions of flips. Each generation gets its own stream derived from the seed, so a
+        // run resumed from a saved snapshot or a rewind sees the same noise again.
+        if self.temperature > 0.0 {
+            let mut noise_rng = StdRng::seed_from_u64(self.seed ^ self.stats.generation.wrapping_add(1).wrapping_mul(NOISE_SEED_MIX));
+            let (rows, cols) = self.soup_patch();
+            let area = rows.len() as f64 * cols.len() as f64;
+            let flips = (area * self.temperature as f64).round() as u64;
+            for _ in 0..flips {
+                let row = noise_rng.gen_range(rows.start, rows.end);
+                let col = noise_rng.gen_range(cols.start, cols.end);
+                let cell = match self.engine.get(row, col) {
+                    Cell::Alive => {
+                        deaths += 1;
+                        Cell::Dead
+                    }
+                    Cell::Dead => {
+                        births += 1;
+                        Cell::Alive
+                    }
+                };
+                self.engine.set(row, col, cell);
+                changed.push((row, col));
+            }
+        }
+        self.stats.record_tick(births, deaths);
+
+        if !self.dirty {
+            self.changed.extend(changed);
+            // Past this point repainting everything is cheaper than tracking cells one by one.
+            let limit = (self.width as u64 * self.height as u64 / 4).min(MAX_TRACKED_CHANGES);
+            if self.changed.len() as u64 > limit {
+                self.dirty = true;
+                self.changed.clear();
+            }
+        }
+    }
+
+    /// Draws the given region with Unicode half blocks, packing two rows into each line.
+    pub fn to_block_text(&self, rows: Range<u32>, cols: Range<u32>) -> String {
+        let mut text = String::new();
+        for top in rows.clone().step_by(2) {
+            for col in cols.clone() {
+                let upper = self.get_cell_state(top, col) == Cell::Alive;
+                let lower = top + 1 < rows.end && self.get_cell_state(top + 1, col) == Cell::Alive;
+                text.push(match (upper, lower) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                });
+            }
+            text.push('\n');
+        }
+        return text;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn noise_on_a_huge_sparse_universe_stays_in_the_soup_patch() {
+        let mut universe = Universe::new(SPARSE_UNIVERSE_SIZE, SPARSE_UNIVERSE_SIZE, EngineKind::Sparse).unwrap();
+        universe.temperature = 0.01;
+        universe.tick();
+
+        let (rows, cols) = universe.soup_patch();
+        let flips = (rows.len() * cols.len()) as u32 / 100;
+        assert!(universe.stats.population > 0);
+        assert!(universe.stats.population <= flips);
+        for (row, col) in universe.engine.live_cells() {
+            assert!(rows.contains(&row) && cols.contains(&col));
+        }
+    }
+
+    #[test]
+    fn stats_follow_the_grid_through_noise() {
+        let mut universe = Universe::new(64, 64, EngineKind::Dense).unwrap();
+        universe.randomize(11, 0.3);
+        universe.temperature = 0.05;
+        for generation in 1..=50 {
+            let before = universe.stats.population;
+            universe.tick();
+            let stats = &universe.stats;
+            assert_eq!(stats.generation, generation);
+            assert_eq!(stats.population, universe.engine.population());
+            assert_eq!(stats.population, before + stats.births - stats.deaths);
+            assert_eq!(stats.history.back(), Some(&stats.population));
+        }
+    }
+
+    #[test]
+    fn undrawn_changes_fall_back_to_a_full_repaint() {
+        let mut universe = Universe::new(SPARSE_UNIVERSE_SIZE, SPARSE_UNIVERSE_SIZE, EngineKind::Sparse).unwrap();
+        universe.randomize(5, 0.5);
+        universe.mark_drawn();
+        // A 50% soup of a million cells changes by more than the limit within a few ticks.
+        for _ in 0..5 {
+            universe.tick();
+            assert!(universe.changed.len() as u64 <= MAX_TRACKED_CHANGES);
+        }
+        assert!(universe.dirty);
+        assert!(universe.changed.is_empty());
+    }
+}
@@ -1,158 +1,473 @@
-
+mod capture;
+mod cli;
+mod headless;
+mod overlay;
+mod render;
+mod theme;
+
+use anyhow::Context as _;
+use capture::Recording;
+use cli::Options;
+use gol_nvg::cycle::{self, CycleDetector};
+use gol_nvg::engine::EngineKind;
+use gol_nvg::history::History;
+use gol_nvg::pattern::{self, Pattern};
+use gol_nvg::rule::{self, Rule};
+use gol_nvg::{snapshot, stats};
+use gol_nvg::{Boundary, Cell, RuleGradient, Universe, SPARSE_UNIVERSE_SIZE, UNIVERSE_HEIGHT, UNIVERSE_WIDTH};
 use nvg::{Align, Color, Context};
-use std::time::Instant;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use render::FrameCache;
+use theme::Theme;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
 const SQUARE_SIZE: u32 = 50;
+const MIN_ZOOM: f32 = 0.1;
+const MAX_ZOOM: f32 = 4.0;
+const ZOOM_STEP: f32 = 1.1;
+const PAN_STEP: f32 = 50.0;
+const HISTORY_LENGTH: usize = 200;
+const TICK_INTERVAL: f32 = 0.1;
+const AMBIENT_TICK_INTERVAL: f32 = 1.0;
+const MIN_TICK_INTERVAL: f32 = 0.01;
+const MAX_TICK_INTERVAL: f32 = 5.0;
+const SPEED_FACTOR: f32 = 1.5;
+const TEMPERATURE_STEP: f32 = 0.001;
+const MAX_TEMPERATURE: f32 = 0.1;
+
+/// Decides when the universe advances a generation, independently of how often frames are drawn.
+pub struct Stepper {
+    interval: f32,
+    paused: bool,
+    pending_steps: u32,
+    last_tick: Instant,
+}
+
+impl Stepper {
+    fn new(interval: f32) -> Stepper {
+        return Stepper {
+            interval,
+            paused: false,
+            pending_steps: 0,
+            last_tick: Instant::now(),
+        };
+    }
+
+    /// Number of generations to run right now; resets the tick clock when any are due.
+    fn steps_due(&mut self) -> u32 {
+        let mut steps = std::mem::replace(&mut self.pending_steps, 0);
+        if !self.paused && (Instant::now() - self.last_tick).as_secs_f32() > self.interval {
+            steps += 1;
+        }
+        if steps > 0 {
+            self.last_tick = Instant::now();
+        }
+        return steps;
+    }
 
-#[repr(u8)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum Cell {
-    Dead = 0,
-    Alive = 1,
+    /// When the next tick is due, or `None` while paused.
+    fn next_tick(&self) -> Option<Instant> {
+        if self.paused {
+            None
+        } else {
+            Some(self.last_tick + Duration::from_secs_f32(self.interval))
+        }
+    }
+
+    fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.last_tick = Instant::now();
+    }
+
+    fn step_once(&mut self) {
+        if self.paused {
+            self.pending_steps += 1;
+        }
+    }
+
+    fn faster(&mut self) {
+        self.interval = (self.interval / SPEED_FACTOR).max(MIN_TICK_INTERVAL);
+    }
+
+    fn slower(&mut self) {
+        self.interval = (self.interval * SPEED_FACTOR).min(MAX_TICK_INTERVAL);
+    }
 }
 
-pub struct Universe {
-    width: u32,
-    height: u32,
-    cells: Vec<Cell>,
-    dirty: bool,
+/// The part of the universe shown in the window.
+///
+/// World coordinates are cell positions multiplied by `SQUARE_SIZE`; `x`/`y` is the world point
+/// at the top left of the window and `zoom` is screen pixels per world unit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    x: f32,
+    y: f32,
+    zoom: f32,
 }
 
-impl Universe {
-
-    fn new(width: u32, height: u32) -> Universe {
-        // let mut rng = rand::thread_rng();
-        return Universe {
-            width,
-            height,
-            cells: (0..(width*height)).map(|i| {
-                // if i % 2 == 0 || i % 7 == 0 {
-                if rand::random::<u8>()%2 == 1 {
-                    Cell::Alive
-                } else {
-                    Cell::Dead
-                }
-            }).collect(),
-            dirty: true,
+impl Camera {
+    /// A camera looking at the middle of the universe, zoomed so cells are `cell_size` pixels.
+    fn centered(universe: &Universe, cell_size: f32, screen_width: f32, screen_height: f32) -> Camera {
+        let zoom = cell_size / SQUARE_SIZE as f32;
+        return Camera {
+            x: ((universe.width * SQUARE_SIZE) as f32 - screen_width / zoom) / 2.0,
+            y: ((universe.height * SQUARE_SIZE) as f32 - screen_height / zoom) / 2.0,
+            zoom,
         };
     }
 
-    fn get_cell_state(&self, row: u32, column: u32) -> Cell {
-        let idx = self.get_index(row, column);
-        return self.cells[idx];
+    fn screen_to_world(&self, x: f32, y: f32) -> (f32, f32) {
+        (x / self.zoom + self.x, y / self.zoom + self.y)
     }
 
-    fn live_neighbors(&self, row: u32, column: u32) -> u8 {
-        let mut count = 0;
-        for drow in ([-1, 0, 1] as [i32; 3]).iter().cloned() {
-            for dcol in ([-1, 0, 1] as [i32; 3]).iter().cloned() {
-                if (drow == 0 && dcol == 0) ||
-                    (drow == -1 && row == 0) ||
-                    (drow == 1 && row == self.height - 1) ||
-                    (dcol == -1 && column == 0) ||
-                    (dcol == 1  && column == self.width - 1) {
-                    continue;
-                }
-                let idx = self.get_index(((row as i32) + drow) as u32, ((column as i32) + dcol) as u32);
-                count += self.cells[idx] as u8;
-            }
-        }
-        return count;
-    }    
+    /// Moves the view by a distance given in screen pixels.
+    fn pan(&mut self, dx: f32, dy: f32) {
+        self.x += dx / self.zoom;
+        self.y += dy / self.zoom;
+    }
 
-    fn get_index(&self, row: u32, column: u32) -> usize {
-        (row * self.width + column) as usize
+    /// Scales the view by `factor`, keeping the world point under the screen position fixed.
+    fn zoom_at(&mut self, factor: f32, x: f32, y: f32) {
+        let (world_x, world_y) = self.screen_to_world(x, y);
+        self.zoom = (self.zoom * factor).max(MIN_ZOOM).min(MAX_ZOOM);
+        self.x = world_x - x / self.zoom;
+        self.y = world_y - y / self.zoom;
     }
-        
-
-    fn tick(&mut self) {
-        self.dirty = false;
-        let mut next = self.cells.clone();
-
-        for row in 0..self.height {
-            for col in 0..self.width {
-                let idx = self.get_index(row, col);            
-                let cell = self.cells[idx];
-                let live_neighbors = self.live_neighbors(row, col);
-
-                let next_cell_state = match (cell, live_neighbors) {
-                    (Cell::Alive, x) if x < 2 => Cell::Dead,
-                    (Cell::Alive, 2) | (Cell::Alive, 3) => Cell::Alive,
-                    (Cell::Alive, x) if x > 3 => Cell::Dead,
-                    (Cell::Dead, 3) => Cell::Alive,
-                    (otherwise_set_same_state, _) => otherwise_set_same_state,
-                };
-
-                if next[idx] != next_cell_state {
-                    self.dirty = true;
-                }
-                next[idx] = next_cell_state;
-            }
-        }
 
-        self.cells = next;
+    fn apply(&self, ctx: &mut Context<nvg_gl::Renderer>) {
+        ctx.scale(self.zoom, self.zoom);
+        ctx.translate(-self.x, -self.y);
     }
 
-    fn debug_print(&self) {
-        for row in 0..self.height {
-            for col in 0..self.width {
-                let cell_state = self.get_cell_state(row, col);
-    
-                if cell_state == Cell::Alive { print!("*"); } else { print!(" "); }
-            }
-            print!("\n");
-        }
-        println!("-----------------------------------------------------\n");        
+    /// Rows and columns that are at least partly on screen, as half open ranges.
+    fn visible_cells(&self, universe: &Universe, screen_width: f32, screen_height: f32) -> (std::ops::Range<u32>, std::ops::Range<u32>) {
+        let (left, top) = self.screen_to_world(0.0, 0.0);
+        let (right, bottom) = self.screen_to_world(screen_width, screen_height);
+        let to_cell = |v: f32, max: u32| ((v / SQUARE_SIZE as f32).max(0.0) as u32).min(max);
+        let rows = to_cell(top, universe.height)..to_cell(bottom + SQUARE_SIZE as f32, universe.height);
+        let cols = to_cell(left, universe.width)..to_cell(right + SQUARE_SIZE as f32, universe.width);
+        return (rows, cols);
     }
 }
 
+/// What the grid pass of a frame draws and how.
+struct View {
+    camera: Camera,
+    theme: Theme,
+    /// Repaint every visible cell rather than only what changed.
+    full: bool,
+    /// At least one generation passed since the previous frame.
+    ticked: bool,
+    width: f32,
+    height: f32,
+    ambient: bool,
+}
 
+/// Everything besides the universe that the HUD reports on.
+struct Hud<'a> {
+    stepper: &'a Stepper,
+    history: &'a History,
+    detector: &'a CycleDetector,
+    stamp: Option<&'a Pattern>,
+    recording: Option<&'a Recording>,
+    fps: f32,
+}
 
 fn init(ctx: &mut Context<nvg_gl::Renderer>) -> anyhow::Result<()> {
     ctx.create_font_from_file("roboto", "fonts/Roboto-Bold.ttf").unwrap();
     Ok(())
 }
 
-fn update(universe: &Universe, width: f32, height: f32, ctx: &mut Context<nvg_gl::Renderer>) -> anyhow::Result<()> {
+/// Paints cells into the frame cache: every visible cell when `full`, otherwise only the cells
+/// that changed since the last frame, plus, after a tick, cells whose color depends on their age.
+fn update(universe: &Universe, view: &View, ctx: &mut Context<nvg_gl::Renderer>) -> anyhow::Result<()> {
+    let View { camera, theme, full, ticked, width, height, ambient } = *view;
 
-    let white_color: Color = Color::rgba(1.0, 1.0, 1.0, 1.0);
-    let orange_color: Color = Color::rgb_i(227, 183, 61);
     let border_color: Color = Color::rgb_i(140, 55, 96);
-    let black_color = Color::rgba(0.0, 0.0, 0.0, 1.0);
+    let ambient_color: Color = Color::rgba_i(227, 183, 61, 96);
 
     let s = nvg::Extent::new(SQUARE_SIZE as f32, SQUARE_SIZE as f32);
 
-    for row in 0..universe.height {
-        for col in 0..universe.width {
-            let cell_state = universe.get_cell_state(row, col);
+    let mut draw_cell = |row: u32, col: u32, cell_state: Cell, age: u16| -> anyhow::Result<()> {
 
-            ctx.begin_path();
-            ctx.stroke_paint(border_color);
-            let p = nvg::Point::new((col * SQUARE_SIZE) as f32, (row * SQUARE_SIZE) as f32);
-            ctx.rect(nvg::Rect::new(p, s));
+        // The ambient overlay only draws live cells so the desktop shows through.
+        if ambient {
             if cell_state == Cell::Alive {
-                ctx.fill_paint(orange_color)
-            } else {
-                 ctx.fill_paint(black_color);
+                ctx.begin_path();
+                let p = nvg::Point::new((col * SQUARE_SIZE) as f32, (row * SQUARE_SIZE) as f32);
+                ctx.rect(nvg::Rect::new(p, s));
+                ctx.fill_paint(ambient_color);
+                ctx.fill()?;
+            }
+            return Ok(());
+        }
+
+        ctx.begin_path();
+        ctx.stroke_paint(border_color);
+        let p = nvg::Point::new((col * SQUARE_SIZE) as f32, (row * SQUARE_SIZE) as f32);
+        ctx.rect(nvg::Rect::new(p, s));
+        ctx.fill_paint(theme.color(cell_state, age));
+        ctx.fill()?;
+        Ok(())
+    };
+
+    // The engine only reports cells that have ever been alive; everything else stays the
+    // background the frame was cleared to.
+    let (rows, cols) = camera.visible_cells(universe, width, height);
+    let mut visible = Vec::new();
+    universe.engine.visit(rows.clone(), cols.clone(), &mut |row, col, cell, age| {
+        if full && !theme.is_background(cell, age) || !full && ticked && theme.fades(cell, age) {
+            visible.push((row, col, cell, age));
+        }
+    });
+    if !full {
+        for &(row, col) in &universe.changed {
+            if rows.contains(&row) && cols.contains(&col) {
+                visible.push((row, col, universe.get_cell_state(row, col), universe.get_age(row, col)));
             }
-            ctx.fill()?;
         }
     }
+    for (row, col, cell, age) in visible {
+        draw_cell(row, col, cell, age)?;
+    }
+
+
+    Ok(())
+}
 
+fn draw_hover(row: u32, col: u32, ctx: &mut Context<nvg_gl::Renderer>) -> anyhow::Result<()> {
+    let white_color: Color = Color::rgba(1.0, 1.0, 1.0, 1.0);
+
+    ctx.begin_path();
+    let p = nvg::Point::new((col * SQUARE_SIZE) as f32, (row * SQUARE_SIZE) as f32);
+    ctx.rect(nvg::Rect::new(p, nvg::Extent::new(SQUARE_SIZE as f32, SQUARE_SIZE as f32)));
+    ctx.stroke_paint(white_color);
+    ctx.stroke_width(2.0);
+    ctx.stroke()?;
+
+    Ok(())
+}
+
+fn draw_stamp_preview(pattern: &Pattern, row: u32, col: u32, ctx: &mut Context<nvg_gl::Renderer>) -> anyhow::Result<()> {
+    let preview_color: Color = Color::rgba(1.0, 1.0, 1.0, 0.4);
+    let s = nvg::Extent::new(SQUARE_SIZE as f32, SQUARE_SIZE as f32);
+
+    ctx.begin_path();
+    for &(dr, dc) in &pattern.cells {
+        let p = nvg::Point::new(((col + dc) * SQUARE_SIZE) as f32, ((row + dr) * SQUARE_SIZE) as f32);
+        ctx.rect(nvg::Rect::new(p, s));
+    }
+    ctx.fill_paint(preview_color);
+    ctx.fill()?;
 
     Ok(())
 }
 
-fn cursor_moved(_x: f32, _y: f32) {
+fn draw_hud(universe: &Universe, hud: &Hud, height: f32, ctx: &mut Context<nvg_gl::Renderer>) -> anyhow::Result<()> {
+    let Hud { stepper, history, detector, stamp, recording, fps } = *hud;
+    let text_color: Color = Color::rgb(1.0, 0.0, 0.0);
+    let panel_color: Color = Color::rgba(0.0, 0.0, 0.0, 0.6);
+    let graph_color: Color = Color::rgb_i(227, 183, 61);
+    let stats = &universe.stats;
+
+    let mut lines = vec![
+        format!(
+            "FPS: {:.2}  Tick: {:.0} ms  Rule: {}  Edges: {:?}{}",
+            fps,
+            stepper.interval * 1000.0,
+            if universe.gradient.is_some() { "gradient".to_string() } else { universe.rule.to_string() },
+            universe.boundary,
+            if stepper.paused { "  (paused)" } else { "" },
+        ),
+        format!(
+            "Generation: {}  Population: {}  Births: {}  Deaths: {}  History: {}",
+            stats.generation, stats.population, stats.births, stats.deaths, history.len(),
+        ),
+    ];
+    lines.push(match detector.activity {
+        cycle::Activity::Active => "State: active".to_string(),
+        activity => format!("State: {} for {} generations", activity, detector.stagnant_for),
+    });
+    if universe.temperature > 0.0 {
+        lines.push(format!("Seed: {}  Temperature: {:.3}", universe.seed, universe.temperature));
+    } else {
+        lines.push(format!("Seed: {}", universe.seed));
+    }
+    if let Some(pattern) = stamp {
+        lines.push(format!(
+            "Stamp: {} (R rotate, X mirror, 0 cancel)",
+            pattern.name.as_deref().unwrap_or("pattern"),
+        ));
+    }
+    if let Some(recording) = recording {
+        lines.push(format!("Recording {} (G to stop)", recording.path));
+    }
+
+    ctx.fill_paint(text_color);
+    ctx.font("roboto");
+    ctx.font_size(50.0);
+    ctx.begin_path();
+    ctx.text_align(Align::TOP | Align::LEFT);
+    for (i, line) in lines.iter().enumerate() {
+        ctx.text(nvg::Point::new(20.0, 10.0 + 50.0 * i as f32), line)?;
+    }
+    ctx.fill()?;
+
+    // Population graph in the bottom left corner, scaled to the largest value shown.
+    let (graph_x, graph_y) = (20.0, height - 140.0);
+    let (graph_width, graph_height) = (400.0, 120.0);
+    ctx.begin_path();
+    ctx.rect(nvg::Rect::new(nvg::Point::new(graph_x, graph_y), nvg::Extent::new(graph_width, graph_height)));
+    ctx.fill_paint(panel_color);
+    ctx.fill()?;
+
+    let max = stats.history.iter().cloned().max().unwrap_or(0).max(1) as f32;
+    let step = graph_width / (stats::POPULATION_HISTORY - 1) as f32;
+    ctx.begin_path();
+    for (i, &population) in stats.history.iter().enumerate() {
+        let p = nvg::Point::new(graph_x + step * i as f32, graph_y + graph_height * (1.0 - population as f32 / max));
+        if i == 0 {
+            ctx.move_to(p);
+        } else {
+            ctx.line_to(p);
+        }
+    }
+    ctx.stroke_paint(graph_color);
+    ctx.stroke_width(2.0);
+    ctx.stroke()?;
+
+    Ok(())
+}
+
+/// Top left cell at which to place `pattern` so that it is centred on the hovered cell.
+fn stamp_origin(pattern: &Pattern, row: u32, col: u32) -> (u32, u32) {
+    (row.saturating_sub(pattern.height / 2), col.saturating_sub(pattern.width / 2))
+}
+
+/// The (row, column) of the cell under a screen position, if there is one.
+///
+/// The frame is laid out in physical pixels (`begin_frame` is given `inner_size`), so the
+/// physical cursor position only needs to go through the camera, not the scale factor.
+fn cell_at(universe: &Universe, camera: &Camera, x: f32, y: f32) -> Option<(u32, u32)> {
+    let (world_x, world_y) = camera.screen_to_world(x, y);
+    if world_x < 0.0 || world_y < 0.0 {
+        return None;
+    }
+    let row = world_y as u32 / SQUARE_SIZE;
+    let col = world_x as u32 / SQUARE_SIZE;
+    if row >= universe.height || col >= universe.width {
+        return None;
+    }
+    return Some((row, col));
+}
+
+/// Builds the universe the options ask for: a saved snapshot, a pattern or a random soup.
+fn initial_universe(options: &Options) -> anyhow::Result<Universe> {
+    if let Some(path) = &options.load {
+        let mut universe = snapshot::load(path, options.engine).with_context(|| format!("failed to load {}", path))?;
+        apply_overrides(&mut universe, options)?;
+        return Ok(universe);
+    }
+
+    let engine = options.engine.unwrap_or_default();
+    let mut universe = match engine {
+        EngineKind::Dense => Universe::new(UNIVERSE_WIDTH, UNIVERSE_HEIGHT, engine)?,
+        EngineKind::Sparse => Universe::new(SPARSE_UNIVERSE_SIZE, SPARSE_UNIVERSE_SIZE, engine)?,
+    };
+    if let Some(path) = &options.pattern {
+        let pattern = Pattern::load(path).with_context(|| format!("failed to load {}", path))?;
+        universe.resize(universe.width.max(pattern.width), universe.height.max(pattern.height))
+            .with_context(|| format!("failed to fit {}", path))?;
+        universe.stamp(&pattern, (universe.height - pattern.height) / 2, (universe.width - pattern.width) / 2);
+        // --rule replaces the pattern's rule below, so only the one that is used gets checked.
+        if let (Some(rule), None) = (pattern.rule, options.rule) {
+            universe.set_rule(rule).with_context(|| format!("failed to load {}", path))?;
+        }
+    } else {
+        universe.randomize(options.seed.unwrap_or_else(rand::random), options.density);
+    }
+    apply_overrides(&mut universe, options)?;
+    return Ok(universe);
+}
+
+/// Applies `--rule` and `--boundary`, which win over whatever a pattern or snapshot specified,
+/// and `--serial`.
+fn apply_overrides(universe: &mut Universe, options: &Options) -> anyhow::Result<()> {
+    universe.parallel = !options.serial;
+    if let Some(rule) = options.rule {
+        universe.set_rule(rule)?;
+    }
+    if let Some(boundary) = options.boundary {
+        universe.boundary = boundary;
+    }
+    return Ok(());
+}
+
+/// A file name for something saved from the window, such as a screenshot. Milliseconds keep
+/// two saves in quick succession from writing to the same file at once.
+fn output_path(extension: &str) -> String {
+    return format!("gol-{}.{}", chrono::Local::now().format("%Y%m%d-%H%M%S-%3f"), extension);
+}
 
+/// Starts a new soup once the universe has been stuck for as long as `--auto-reseed` allows.
+/// The new seed follows from the old one, so a run started with `--seed` reseeds the same way
+/// every time. Returns whether it reseeded, in which case any history is stale.
+fn auto_reseed(universe: &mut Universe, detector: &mut CycleDetector, options: &Options) -> bool {
+    if options.auto_reseed.map_or(false, |limit| detector.stagnant_for >= limit) {
+        let seed = StdRng::seed_from_u64(universe.seed).gen();
+        universe.randomize(seed, options.density);
+        detector.reset();
+        return true;
+    }
+    return false;
 }
 
 fn main() {
+    let options = Options::parse();
+    let mut universe = match initial_universe(&options) {
+        Ok(universe) => universe,
+        Err(err) => {
+            eprintln!("Error: {:#}", err);
+            std::process::exit(1);
+        }
+    };
+    if let Some(generations) = options.bench {
+        headless::bench(&mut universe, generations);
+        return;
+    }
+    if options.headless {
+        headless::run(universe, &options);
+        return;
+    }
+
     let mut el = glutin::event_loop::EventLoop::new();
     // let wb = glutin::window::WindowBuilder::new().with_dimensions(glutin::dpi::LogicalSize::new(1024.0, 768.0));
-    let wb = glutin::window::WindowBuilder::new().with_fullscreen(Some(glutin::window::Fullscreen::Borderless(el.primary_monitor())));
+    let ambient = options.ambient;
+    let wb = if ambient {
+        glutin::window::WindowBuilder::new()
+            .with_title("gol-nvg")
+            .with_maximized(true)
+            .with_decorations(false)
+            .with_transparent(true)
+            .with_always_on_top(true)
+    } else if let Some((width, height)) = options.windowed {
+        glutin::window::WindowBuilder::new()
+            .with_title("gol-nvg")
+            .with_inner_size(glutin::dpi::PhysicalSize::new(width, height))
+    } else {
+        glutin::window::WindowBuilder::new().with_fullscreen(Some(glutin::window::Fullscreen::Borderless(el.primary_monitor())))
+    };
+    let mut stepper = Stepper::new(options.tick_interval.unwrap_or(if ambient { AMBIENT_TICK_INTERVAL } else { TICK_INTERVAL }));
     let windowed_context = glutin::ContextBuilder::new().build_windowed(wb, &el).unwrap();
     let windowed_context = unsafe { windowed_context.make_current().unwrap() };
+    if ambient {
+        // An overlay that swallows clicks would block the desktop, so refuse to run without.
+        if let Err(err) = overlay::make_click_through(windowed_context.window()) {
+            eprintln!("Failed to make the ambient window click-through: {}", err);
+            std::process::exit(1);
+        }
+    }
     gl::load_with(|p| windowed_context.get_proc_address(p) as *const _);
 
     let renderer = nvg_gl::Renderer::create().unwrap();
@@ -162,21 +477,100 @@ fn main() {
 
     let mut total_frames = 0;
     let start_time = Instant::now();
-    let mut last_time = Instant::now();
+    let mut painting: Option<Cell> = None;
+    let mut cursor_position: Option<(f32, f32)> = None;
+    let mut dragging = false;
+    let library: Vec<Pattern> = pattern::LIBRARY.iter().map(|rle| Pattern::from_rle(rle).unwrap()).collect();
+    let mut stamp: Option<Pattern> = None;
+    let mut theme = Theme::Classic;
+    let mut show_hud = true;
+    let mut frame_cache = FrameCache::new();
+    let mut last_view: Option<(Camera, Theme)> = None;
+    let mut last_generation = 0;
+    let mut screenshot = false;
+    let mut recording: Option<Recording> = None;
+    let mut recorded_generation: Option<u64> = None;
+    // Screenshots and recordings still being written, waited for before exiting.
+    let mut writers: Vec<JoinHandle<()>> = Vec::new();
+    let mut history = History::new(options.history);
+    let mut detector = CycleDetector::new();
     
     let inner_size = windowed_context.window().inner_size();
-    let mut universe: Universe = Universe::new(inner_size.width/SQUARE_SIZE, inner_size.height/SQUARE_SIZE);
+    let mut camera = Camera::centered(&universe, options.cell_size, inner_size.width as f32, inner_size.height as f32);
+    let mut preset = rule::PRESETS.iter().position(|p| Rule::parse(p).unwrap() == universe.rule);
 
     el.run(move |event, _, control_flow| {
         // println!("{:?}", event);
-        *control_flow = glutin::event_loop::ControlFlow::Poll;
+        *control_flow = if ambient {
+            match stepper.next_tick() {
+                Some(deadline) => glutin::event_loop::ControlFlow::WaitUntil(deadline),
+                None => glutin::event_loop::ControlFlow::Wait,
+            }
+        } else {
+            glutin::event_loop::ControlFlow::Poll
+        };
 
         match event {
-            glutin::event::Event::LoopDestroyed => return,
+            glutin::event::Event::LoopDestroyed => {
+                // The event loop exits the process once this returns, so finish any files first.
+                writers.extend(recording.take().map(Recording::finish));
+                for writer in writers.drain(..) {
+                    let _ = writer.join();
+                }
+                return;
+            }
             glutin::event::Event::WindowEvent { event, .. } => match event {
                 glutin::event::WindowEvent::Resized(physical_size) => {
                     windowed_context.resize(physical_size);
-                    universe = Universe::new(physical_size.width/SQUARE_SIZE, physical_size.height/SQUARE_SIZE);
+                }
+                glutin::event::WindowEvent::CursorMoved { position, .. } => {
+                    let (x, y) = (position.x as f32, position.y as f32);
+                    if let (true, Some((last_x, last_y))) = (dragging, cursor_position) {
+                        camera.pan(last_x - x, last_y - y);
+                    }
+                    cursor_position = Some((x, y));
+                    if let (Some(cell), Some((row, col))) = (painting, cell_at(&universe, &camera, x, y)) {
+                        universe.set_cell(row, col, cell);
+                    }
+                }
+                glutin::event::WindowEvent::CursorLeft { .. } => {
+                    cursor_position = None;
+                    painting = None;
+                    dragging = false;
+                }
+                glutin::event::WindowEvent::MouseWheel { delta, .. } => {
+                    let lines = match delta {
+                        glutin::event::MouseScrollDelta::LineDelta(_, y) => y,
+                        glutin::event::MouseScrollDelta::PixelDelta(position) => position.y as f32 / 20.0,
+                    };
+                    let (x, y) = cursor_position.unwrap_or((0.0, 0.0));
+                    camera.zoom_at(ZOOM_STEP.powf(lines), x, y);
+                }
+                glutin::event::WindowEvent::MouseInput { state, button: glutin::event::MouseButton::Middle, .. } => {
+                    dragging = state == glutin::event::ElementState::Pressed;
+                }
+                glutin::event::WindowEvent::MouseInput {
+                    state: glutin::event::ElementState::Pressed,
+                    button: glutin::event::MouseButton::Left,
+                    ..
+                } if stamp.is_some() => {
+                    let hovered_cell = cursor_position.and_then(|(x, y)| cell_at(&universe, &camera, x, y));
+                    if let (Some(pattern), Some((row, col))) = (&stamp, hovered_cell) {
+                        let (row, col) = stamp_origin(pattern, row, col);
+                        universe.stamp(pattern, row, col);
+                    }
+                }
+                glutin::event::WindowEvent::MouseInput { state, button, .. } => {
+                    painting = match (button, state) {
+                        (glutin::event::MouseButton::Left, glutin::event::ElementState::Pressed) => Some(Cell::Alive),
+                        (glutin::event::MouseButton::Right, glutin::event::ElementState::Pressed) => Some(Cell::Dead),
+                        (_, glutin::event::ElementState::Released) => None,
+                        _ => painting,
+                    };
+                    let hovered_cell = cursor_position.and_then(|(x, y)| cell_at(&universe, &camera, x, y));
+                    if let (Some(cell), Some((row, col))) = (painting, hovered_cell) {
+                        universe.set_cell(row, col, cell);
+                    }
                 }
                 glutin::event::WindowEvent::CloseRequested => {
                     *control_flow = glutin::event_loop::ControlFlow::Exit
@@ -199,6 +593,91 @@ fn main() {
                             windowed_context.window().set_fullscreen(None);
                         }
                     }
+                    (glutin::event::VirtualKeyCode::Space, glutin::event::ElementState::Pressed) => stepper.toggle_pause(),
+                    (glutin::event::VirtualKeyCode::N, glutin::event::ElementState::Pressed) => stepper.step_once(),
+                    (glutin::event::VirtualKeyCode::Back, glutin::event::ElementState::Pressed) => {
+                        if stepper.paused && history.rewind(&mut universe) {
+                            detector.reset();
+                        }
+                    }
+                    (glutin::event::VirtualKeyCode::Left, glutin::event::ElementState::Pressed) => camera.pan(-PAN_STEP, 0.0),
+                    (glutin::event::VirtualKeyCode::Right, glutin::event::ElementState::Pressed) => camera.pan(PAN_STEP, 0.0),
+                    (glutin::event::VirtualKeyCode::Up, glutin::event::ElementState::Pressed) => camera.pan(0.0, -PAN_STEP),
+                    (glutin::event::VirtualKeyCode::Down, glutin::event::ElementState::Pressed) => camera.pan(0.0, PAN_STEP),
+                    (glutin::event::VirtualKeyCode::Equals, glutin::event::ElementState::Pressed) => stepper.faster(),
+                    (glutin::event::VirtualKeyCode::Minus, glutin::event::ElementState::Pressed) => stepper.slower(),
+                    (glutin::event::VirtualKeyCode::Key1, glutin::event::ElementState::Pressed) => stamp = Some(library[0].clone()),
+                    (glutin::event::VirtualKeyCode::Key2, glutin::event::ElementState::Pressed) => stamp = Some(library[1].clone()),
+                    (glutin::event::VirtualKeyCode::Key3, glutin::event::ElementState::Pressed) => stamp = Some(library[2].clone()),
+                    (glutin::event::VirtualKeyCode::Key4, glutin::event::ElementState::Pressed) => stamp = Some(library[3].clone()),
+                    (glutin::event::VirtualKeyCode::Key5, glutin::event::ElementState::Pressed) => stamp = Some(library[4].clone()),
+                    (glutin::event::VirtualKeyCode::Key0, glutin::event::ElementState::Pressed) => stamp = None,
+                    (glutin::event::VirtualKeyCode::R, glutin::event::ElementState::Pressed) => {
+                        stamp = stamp.as_ref().map(Pattern::rotated);
+                    }
+                    (glutin::event::VirtualKeyCode::X, glutin::event::ElementState::Pressed) => {
+                        stamp = stamp.as_ref().map(Pattern::mirrored);
+                    }
+                    (glutin::event::VirtualKeyCode::H, glutin::event::ElementState::Pressed) => show_hud = !show_hud,
+                    (glutin::event::VirtualKeyCode::T, glutin::event::ElementState::Pressed) => theme = theme.next(),
+                    (glutin::event::VirtualKeyCode::E, glutin::event::ElementState::Pressed) => {
+                        let path = output_path("rle");
+                        match std::fs::write(&path, Pattern::from_universe(&universe).to_rle()) {
+                            Ok(()) => println!("Saved {}", path),
+                            Err(err) => eprintln!("Failed to save {}: {}", path, err),
+                        }
+                    }
+                    (glutin::event::VirtualKeyCode::W, glutin::event::ElementState::Pressed) => {
+                        let path = output_path("json");
+                        match snapshot::save(&universe, &path) {
+                            Ok(()) => println!("Saved {}", path),
+                            Err(err) => eprintln!("Failed to save {}: {}", path, err),
+                        }
+                    }
+                    (glutin::event::VirtualKeyCode::L, glutin::event::ElementState::Pressed) => {
+                        let next = preset.map_or(0, |i| (i + 1) % rule::PRESETS.len());
+                        preset = Some(next);
+                        if let Err(err) = universe.set_rule(Rule::parse(rule::PRESETS[next]).unwrap()) {
+                            eprintln!("Can't switch to {}: {}", rule::PRESETS[next], err);
+                        }
+                    }
+                    (glutin::event::VirtualKeyCode::B, glutin::event::ElementState::Pressed) => {
+                        universe.boundary = match universe.boundary {
+                            Boundary::Dead => Boundary::Wrap,
+                            Boundary::Wrap => Boundary::Dead,
+                        };
+                    }
+                    (glutin::event::VirtualKeyCode::S, glutin::event::ElementState::Pressed) => screenshot = true,
+                    (glutin::event::VirtualKeyCode::G, glutin::event::ElementState::Pressed) => {
+                        if let Some(finished) = recording.take() {
+                            writers.push(finished.finish());
+                        } else {
+                            let size = windowed_context.window().inner_size();
+                            let path = output_path("gif");
+                            match Recording::start(path.clone(), size.width, size.height) {
+                                Ok(started) => recording = Some(started),
+                                Err(err) => eprintln!("Failed to record {}: {}", path, err),
+                            }
+                            recorded_generation = None;
+                        }
+                    }
+                    (glutin::event::VirtualKeyCode::V, glutin::event::ElementState::Pressed) => {
+                        universe.gradient = match universe.gradient {
+                            Some(_) => None,
+                            None => Some(RuleGradient::default()),
+                        };
+                    }
+                    (glutin::event::VirtualKeyCode::RBracket, glutin::event::ElementState::Pressed) => {
+                        universe.temperature = (universe.temperature + TEMPERATURE_STEP).min(MAX_TEMPERATURE);
+                    }
+                    (glutin::event::VirtualKeyCode::LBracket, glutin::event::ElementState::Pressed) => {
+                        // Snap to zero so float drift can't leave a tiny residual temperature behind.
+                        universe.temperature = if universe.temperature < 1.5 * TEMPERATURE_STEP {
+                            0.0
+                        } else {
+                            universe.temperature - TEMPERATURE_STEP
+                        };
+                    }
                     _ => (),
                 },                    
                 _ => (),
@@ -210,18 +689,38 @@ fn main() {
             _ => (),
         }
 
-        let time_diff = (Instant::now() - last_time).as_secs_f32();
-        if time_diff > 0.1 {
-            // println!("Tick {:?}\n", (Instant::now() - last_time));
-            last_time = Instant::now();
+        for _ in 0..stepper.steps_due() {
+            history.record(&universe);
             universe.tick();
-            // universe.debug_print();
+            detector.record(&universe);
+            if auto_reseed(&mut universe, &mut detector, &options) {
+                history.clear();
+            }
         }
 
         let size = windowed_context.window().inner_size();
         let device_pixel_ratio = windowed_context.window().scale_factor() as f32;
+        // A minimised window has nothing to draw into.
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+
+        // Repaint the cached grid from scratch whenever what is on screen moved or was restyled.
+        // The ambient overlay can't paint over cells with transparency, so it always starts over.
+        let reused = match frame_cache.bind(size.width, size.height) {
+            Ok(reused) => reused,
+            Err(err) => {
+                eprintln!("Error: {:#}", err);
+                *control_flow = glutin::event_loop::ControlFlow::Exit;
+                return;
+            }
+        };
+        let view = (camera, theme);
+        let full = !reused || universe.dirty || ambient || last_view != Some(view);
+        let ticked = last_generation != universe.stats.generation;
+        last_view = Some(view);
+        last_generation = universe.stats.generation;
 
-        
         unsafe {
             gl::Viewport(
                 0,
@@ -229,36 +728,71 @@ fn main() {
                 (size.width as f32) as i32,
                 (size.height as f32) as i32,
             );
-            gl::ClearColor(0.0, 0.0, 0.0, 1.0);
-            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+            if full {
+                gl::ClearColor(0.0, 0.0, 0.0, if ambient { 0.0 } else { 1.0 });
+                gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+            }
         }
 
-        context
-            .begin_frame(
-                nvg::Extent {
-                    width: size.width as f32,
-                    height: size.height as f32,
-                },
-                device_pixel_ratio,
-            )
-            .unwrap();
-
-        if universe.dirty {
-            context.save();
-            update(&universe, size.width as f32, size.height as f32, &mut context)
-                .unwrap();
-            context.restore();
+        let frame_extent = nvg::Extent::new(size.width as f32, size.height as f32);
+        context.begin_frame(frame_extent, device_pixel_ratio).unwrap();
+        context.save();
+        camera.apply(&mut context);
+        let grid_view = View { camera, theme, full, ticked, width: size.width as f32, height: size.height as f32, ambient };
+        update(&universe, &grid_view, &mut context).unwrap();
+        context.restore();
+        context.end_frame().unwrap();
+        universe.mark_drawn();
+
+        if screenshot {
+            let path = output_path("png");
+            writers.push(capture::save_png(frame_cache.read_pixels(), path));
+            screenshot = false;
+        }
+        if let Some(recording) = &mut recording {
+            let generation = universe.stats.generation;
+            let due = recorded_generation.map_or(true, |recorded| {
+                generation.max(recorded) - generation.min(recorded) >= options.gif_every as u64
+            });
+            if due {
+                let delay = Duration::from_secs_f32(stepper.interval * options.gif_every as f32);
+                recording.push(frame_cache.read_pixels(), delay);
+                recorded_generation = Some(generation);
+            }
         }
 
+        frame_cache.present();
+        unsafe {
+            gl::Clear(gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+        }
+
+        context.begin_frame(nvg::Extent::new(size.width as f32, size.height as f32), device_pixel_ratio).unwrap();
+        context.save();
+        camera.apply(&mut context);
+        let hovered_cell = cursor_position.and_then(|(x, y)| cell_at(&universe, &camera, x, y));
+        match (&stamp, hovered_cell) {
+            (Some(pattern), Some((row, col))) => {
+                let (row, col) = stamp_origin(pattern, row, col);
+                draw_stamp_preview(pattern, row, col, &mut context).unwrap();
+            }
+            (None, Some((row, col))) => draw_hover(row, col, &mut context).unwrap(),
+            _ => (),
+        }
+        context.restore();
+
         total_frames += 1;
         let fps = (total_frames as f32) / (Instant::now() - start_time).as_secs_f32();
-        context.fill_paint(Color::rgb(1.0, 0.0, 0.0));
-        context.font("roboto");
-        context.font_size(50.0);
-        context.begin_path();
-        context.text_align(Align::TOP | Align::LEFT);
-        context.text((20, 10), format!("FPS: {:.2}", fps)).unwrap();
-        context.fill().unwrap();
+        if show_hud && !ambient {
+            let hud = Hud {
+                stepper: &stepper,
+                history: &history,
+                detector: &detector,
+                stamp: stamp.as_ref(),
+                recording: recording.as_ref(),
+                fps,
+            };
+            draw_hud(&universe, &hud, size.height as f32, &mut context).unwrap();
+        }
 
         context.end_frame().unwrap();
         windowed_context.swap_buffers().unwrap();        
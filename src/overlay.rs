@@ -0,0 +1,61 @@
+use glutin::window::Window;
+
+/// Lets mouse clicks pass through the window to whatever is underneath, so the ambient overlay
+/// never gets in the way. winit has no API for this, so it goes through each platform's
+/// native window handle.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn make_click_through(window: &Window) -> anyhow::Result<()> {
+    use glutin::platform::unix::WindowExtUnix;
+    use x11_dl::{xfixes, xlib};
+
+    /// `ShapeInput` from the X Shape extension: the region of the window that receives input.
+    const SHAPE_INPUT: i32 = 2;
+
+    let (display, window) = match (window.xlib_display(), window.xlib_window()) {
+        (Some(display), Some(window)) => (display as *mut xlib::Display, window),
+        _ => anyhow::bail!("click-through needs X11; on Wayland run with WINIT_UNIX_BACKEND=x11"),
+    };
+    let xlib = xlib::Xlib::open()?;
+    // x11-dl names the XFixes function table `Xlib` as well.
+    let xfixes = xfixes::Xlib::open()?;
+    unsafe {
+        // An empty input region means no part of the window takes clicks.
+        let region = (xfixes.XFixesCreateRegion)(display, std::ptr::null_mut(), 0);
+        (xfixes.XFixesSetWindowShapeRegion)(display, window, SHAPE_INPUT, 0, 0, region);
+        (xfixes.XFixesDestroyRegion)(display, region);
+        (xlib.XFlush)(display);
+    }
+    return Ok(());
+}
+
+#[cfg(windows)]
+pub fn make_click_through(window: &Window) -> anyhow::Result<()> {
+    use glutin::platform::windows::WindowExtWindows;
+    use winapi::shared::windef::HWND;
+    use winapi::um::winuser::{GetWindowLongPtrW, SetLayeredWindowAttributes, SetWindowLongPtrW};
+    use winapi::um::winuser::{GWL_EXSTYLE, LWA_ALPHA, WS_EX_LAYERED, WS_EX_TRANSPARENT};
+
+    let hwnd = window.hwnd() as HWND;
+    unsafe {
+        let style = GetWindowLongPtrW(hwnd, GWL_EXSTYLE);
+        SetWindowLongPtrW(hwnd, GWL_EXSTYLE, style | (WS_EX_LAYERED | WS_EX_TRANSPARENT) as isize);
+        // A layered window stays invisible until its opacity has been set once.
+        if SetLayeredWindowAttributes(hwnd, 0, 255, LWA_ALPHA) == 0 {
+            anyhow::bail!("SetLayeredWindowAttributes failed: {}", std::io::Error::last_os_error());
+        }
+    }
+    return Ok(());
+}
+
+#[cfg(target_os = "macos")]
+pub fn make_click_through(window: &Window) -> anyhow::Result<()> {
+    use glutin::platform::macos::WindowExtMacOS;
+    use objc::runtime::{Object, YES};
+    use objc::{msg_send, sel, sel_impl};
+
+    let ns_window = window.ns_window() as *mut Object;
+    unsafe {
+        let _: () = msg_send![ns_window, setIgnoresMouseEvents: YES];
+    }
+    return Ok(());
+}
@@ -0,0 +1,264 @@
+use crate::rule::Rule;
+use crate::Universe;
+use std::path::Path;
+
+/// Longest line written when exporting RLE, as recommended by the format.
+const RLE_LINE_LENGTH: usize = 70;
+
+/// Classic patterns available as stamps, selected with the number keys in this order.
+pub const LIBRARY: [&str; 5] = [
+    "#N Glider\nx = 3, y = 3\nbo$2bo$3o!",
+    "#N LWSS\nx = 5, y = 4\nbo2bo$o4b$o3bo$4o!",
+    "#N Pulsar\nx = 13, y = 13\n2b3o3b3o2b2$o4bobo4bo$o4bobo4bo$o4bobo4bo$2b3o3b3o2b2$2b3o3b3o2b$o4bob\no4bo$o4bobo4bo$o4bobo4bo2$2b3o3b3o!",
+    "#N Gosper glider gun\nx = 36, y = 9\n24bo$22bobo$12b2o6b2o12b2o$11bo3bo4b2o12b2o$2o8bo5bo3b2o$2o8bo3bob2o4b\nobo$10bo5bo7bo$11bo3bo$12b2o!",
+    "#N R-pentomino\nx = 3, y = 3\nb2o$2o$bo!",
+];
+
+/// A set of live cells given as (row, column) offsets from the pattern's top left corner.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pattern {
+    pub name: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub cells: Vec<(u32, u32)>,
+    /// Rule named in the file header, if any.
+    pub rule: Option<Rule>,
+}
+
+impl Pattern {
+    /// Loads a plaintext pattern if the file ends in `.cells`, otherwise an RLE pattern.
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Pattern> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("cells") => Pattern::from_plaintext(&text),
+            _ => Pattern::from_rle(&text),
+        }
+    }
+
+    /// Parses the plaintext format: `!` comment lines, then rows of `.` (dead) and `O` (alive).
+    pub fn from_plaintext(text: &str) -> anyhow::Result<Pattern> {
+        let mut cells = Vec::new();
+        let mut width = 0;
+        let mut height = 0;
+        let name = text.lines().find_map(|line| line.strip_prefix("!Name:")).map(|name| name.trim().to_string());
+        for line in text.lines().filter(|line| !line.starts_with('!')) {
+            let line = line.trim_end();
+            for (col, c) in line.chars().enumerate() {
+                match c {
+                    'O' | 'o' | '*' => cells.push((height, col as u32)),
+                    '.' => (),
+                    _ => anyhow::bail!("unexpected character {:?} in plaintext pattern", c),
+                }
+            }
+            width = width.max(line.chars().count() as u32);
+            height += 1;
+        }
+        return Ok(Pattern { name, width, height, cells, rule: None });
+    }
+
+    /// Parses run length encoded patterns with an `x = .., y = .., rule = ..` header.
+    pub fn from_rle(text: &str) -> anyhow::Result<Pattern> {
+        let name = text.lines().find_map(|line| line.trim().strip_prefix("#N")).map(|name| name.trim().to_string());
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+        let header = lines.next().ok_or_else(|| anyhow::anyhow!("RLE pattern is empty"))?;
+        let mut width = None;
+        let mut height = None;
+        let mut rule = None;
+        for field in header.split(',') {
+            let mut kv = field.splitn(2, '=').map(str::trim);
+            match (kv.next(), kv.next()) {
+                (Some("x"), Some(value)) => width = Some(value.parse::<u32>()?),
+                (Some("y"), Some(value)) => height = Some(value.parse::<u32>()?),
+                (Some("rule"), Some(value)) => rule = Some(Rule::parse(value)?),
+                _ => anyhow::bail!("malformed RLE header {:?}", header),
+            }
+        }
+        let (width, height) = match (width, height) {
+            (Some(width), Some(height)) => (width, height),
+            _ => anyhow::bail!("RLE header {:?} is missing x or y", header),
+        };
+
+        let mut cells = Vec::new();
+        let (mut row, mut col) = (0u32, 0u32);
+        let mut count: Option<u32> = None;
+        let too_long = || anyhow::anyhow!("RLE run count is too large");
+        'body: for line in lines {
+            for c in line.chars() {
+                if let Some(digit) = c.to_digit(10) {
+                    let n = count.unwrap_or(0).checked_mul(10).and_then(|n| n.checked_add(digit)).ok_or_else(too_long)?;
+                    count = Some(n);
+                    continue;
+                }
+                let run = count.take().unwrap_or(1);
+                match c {
+                    'b' | '.' => col = col.checked_add(run).ok_or_else(too_long)?,
+                    '$' => {
+                        row = row.checked_add(run).ok_or_else(too_long)?;
+                        col = 0;
+                    }
+                    '!' => break 'body,
+                    c if c.is_whitespace() => (),
+                    // Any other letter is a live state in multi-state RLE.
+                    c if c.is_ascii_alphabetic() => {
+                        // Check before storing anything, so a huge run can't allocate past the grid.
+                        let end = col.checked_add(run).ok_or_else(too_long)?;
+                        if row >= height || end > width {
+                            anyhow::bail!("RLE pattern does not fit its {}x{} header", width, height);
+                        }
+                        cells.extend((col..end).map(|col| (row, col)));
+                        col = end;
+                    }
+                    _ => anyhow::bail!("unexpected character {:?} in RLE pattern", c),
+                }
+            }
+        }
+        return Ok(Pattern { name, width, height, cells, rule });
+    }
+
+    /// Captures the bounding box of every live cell in the universe.
+    pub fn from_universe(universe: &Universe) -> Pattern {
+        let live = universe.engine.live_cells();
+        let top = live.iter().map(|&(row, _)| row).min().unwrap_or(0);
+        let left = live.iter().map(|&(_, col)| col).min().unwrap_or(0);
+        let bottom = live.iter().map(|&(row, _)| row + 1).max().unwrap_or(0);
+        let right = live.iter().map(|&(_, col)| col + 1).max().unwrap_or(0);
+        return Pattern {
+            name: None,
+            width: right - left,
+            height: bottom - top,
+            cells: live.into_iter().map(|(row, col)| (row - top, col - left)).collect(),
+            rule: Some(universe.rule),
+        };
+    }
+
+    /// The pattern turned a quarter turn clockwise.
+    pub fn rotated(&self) -> Pattern {
+        return Pattern {
+            name: self.name.clone(),
+            width: self.height,
+            height: self.width,
+            cells: self.cells.iter().map(|&(row, col)| (col, self.height - 1 - row)).collect(),
+            rule: self.rule,
+        };
+    }
+
+    /// The pattern flipped left to right.
+    pub fn mirrored(&self) -> Pattern {
+        return Pattern {
+            name: self.name.clone(),
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().map(|&(row, col)| (row, self.width - 1 - col)).collect(),
+            rule: self.rule,
+        };
+    }
+
+    pub fn to_rle(&self) -> String {
+        let mut rows = vec![Vec::new(); self.height as usize];
+        for &(row, col) in &self.cells {
+            rows[row as usize].push(col);
+        }
+
+        // Runs of (count, tag); trailing dead cells in a row are dropped and row ends merge.
+        let mut runs: Vec<(u32, char)> = Vec::new();
+        let mut push = |count: u32, tag: char| match runs.last_mut() {
+            Some((n, last)) if *last == tag => *n += count,
+            _ => runs.push((count, tag)),
+        };
+        for (i, cols) in rows.iter_mut().enumerate() {
+            cols.sort();
+            let mut next = 0;
+            for &col in cols.iter() {
+                if col > next {
+                    push(col - next, 'b');
+                }
+                push(1, 'o');
+                next = col + 1;
+            }
+            if i + 1 < self.height as usize {
+                push(1, '$');
+            }
+        }
+        while let Some((_, '$')) = runs.last() {
+            runs.pop();
+        }
+
+        let mut out = match &self.name {
+            Some(name) => format!("#N {}\n", name),
+            None => String::new(),
+        };
+        out += &match self.rule {
+            Some(rule) => format!("x = {}, y = {}, rule = {}\n", self.width, self.height, rule),
+            None => format!("x = {}, y = {}\n", self.width, self.height),
+        };
+        let mut line = String::new();
+        for (count, tag) in runs.into_iter().chain(std::iter::once((1, '!'))) {
+            let run = if count > 1 { format!("{}{}", count, tag) } else { tag.to_string() };
+            if line.len() + run.len() > RLE_LINE_LENGTH {
+                out.push_str(&line);
+                out.push('\n');
+                line.clear();
+            }
+            line.push_str(&run);
+        }
+        out.push_str(&line);
+        out.push('\n');
+        return out;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(mut cells: Vec<(u32, u32)>) -> Vec<(u32, u32)> {
+        cells.sort();
+        return cells;
+    }
+
+    /// Writes the pattern in the plaintext format `from_plaintext` reads.
+    fn to_plaintext(pattern: &Pattern) -> String {
+        let mut grid = vec![vec!['.'; pattern.width as usize]; pattern.height as usize];
+        for &(row, col) in &pattern.cells {
+            grid[row as usize][col as usize] = 'O';
+        }
+        let mut text = format!("!Name: {}\n", pattern.name.as_deref().unwrap_or(""));
+        for row in grid {
+            text.extend(row);
+            text.push('\n');
+        }
+        return text;
+    }
+
+    #[test]
+    fn library_round_trips_through_rle() {
+        for rle in &LIBRARY {
+            let pattern = Pattern::from_rle(rle).unwrap();
+            assert!(!pattern.cells.is_empty());
+            let again = Pattern::from_rle(&pattern.to_rle()).unwrap();
+            assert_eq!(again.name, pattern.name);
+            assert_eq!((again.width, again.height), (pattern.width, pattern.height));
+            assert_eq!(sorted(again.cells), sorted(pattern.cells));
+        }
+    }
+
+    #[test]
+    fn library_round_trips_through_plaintext() {
+        for rle in &LIBRARY {
+            let pattern = Pattern::from_rle(rle).unwrap();
+            let again = Pattern::from_plaintext(&to_plaintext(&pattern)).unwrap();
+            assert_eq!(again.name, pattern.name);
+            assert_eq!((again.width, again.height), (pattern.width, pattern.height));
+            assert_eq!(sorted(again.cells), sorted(pattern.cells));
+        }
+    }
+
+    #[test]
+    fn rejects_runs_past_the_header() {
+        assert!(Pattern::from_rle("x = 3, y = 1\n4o!").is_err());
+        assert!(Pattern::from_rle("x = 3, y = 1\n$o!").is_err());
+        assert!(Pattern::from_rle("x = 3, y = 1\n99999999999o!").is_err());
+    }
+}
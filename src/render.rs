@@ -0,0 +1,96 @@
+use crate::capture::Image;
+use gl::types::GLuint;
+
+/// An offscreen framebuffer holding the grid as it was last drawn, so a frame only has to
+/// repaint the cells that changed before copying it to the window.
+pub struct FrameCache {
+    framebuffer: GLuint,
+    color: GLuint,
+    depth_stencil: GLuint,
+    width: u32,
+    height: u32,
+}
+
+impl FrameCache {
+    pub fn new() -> FrameCache {
+        return FrameCache { framebuffer: 0, color: 0, depth_stencil: 0, width: 0, height: 0 };
+    }
+
+    /// Makes the cache the current draw target. Returns false when its contents are gone (first
+    /// use or a new window size) and the whole grid has to be drawn again. Fails for sizes the
+    /// driver can't back with a complete framebuffer, such as 0x0.
+    pub fn bind(&mut self, width: u32, height: u32) -> anyhow::Result<bool> {
+        let reused = self.framebuffer != 0 && self.width == width && self.height == height;
+        unsafe {
+            if !reused {
+                self.release();
+                gl::GenFramebuffers(1, &mut self.framebuffer);
+                gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+
+                // nvg needs a stencil buffer to fill paths.
+                gl::GenRenderbuffers(1, &mut self.color);
+                gl::BindRenderbuffer(gl::RENDERBUFFER, self.color);
+                gl::RenderbufferStorage(gl::RENDERBUFFER, gl::RGBA8, width as i32, height as i32);
+                gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::RENDERBUFFER, self.color);
+
+                gl::GenRenderbuffers(1, &mut self.depth_stencil);
+                gl::BindRenderbuffer(gl::RENDERBUFFER, self.depth_stencil);
+                gl::RenderbufferStorage(gl::RENDERBUFFER, gl::DEPTH24_STENCIL8, width as i32, height as i32);
+                gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::DEPTH_STENCIL_ATTACHMENT, gl::RENDERBUFFER, self.depth_stencil);
+
+                gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+                let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+                if status != gl::FRAMEBUFFER_COMPLETE {
+                    gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+                    self.release();
+                    anyhow::bail!("{}x{} offscreen framebuffer is incomplete (status {:#x})", width, height, status);
+                }
+                self.width = width;
+                self.height = height;
+            }
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+        }
+        return Ok(reused);
+    }
+
+    /// Copies the cached grid into the window's framebuffer and leaves that bound for overlays.
+    pub fn present(&self) {
+        let (width, height) = (self.width as i32, self.height as i32);
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.framebuffer);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, 0);
+            gl::BlitFramebuffer(0, 0, width, height, 0, 0, width, height, gl::COLOR_BUFFER_BIT, gl::NEAREST);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    /// Reads the cached grid back from the GPU, without the overlays drawn on top of it.
+    pub fn read_pixels(&self) -> Image {
+        let row_length = self.width as usize * 4;
+        let mut rgba = vec![0u8; row_length * self.height as usize];
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.framebuffer);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(0, 0, self.width as i32, self.height as i32, gl::RGBA, gl::UNSIGNED_BYTE, rgba.as_mut_ptr() as *mut _);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+        }
+        // GL returns the bottom row first.
+        let rgba = rgba.chunks(row_length).rev().flatten().cloned().collect();
+        return Image { width: self.width, height: self.height, rgba };
+    }
+
+    unsafe fn release(&mut self) {
+        if self.framebuffer != 0 {
+            gl::DeleteFramebuffers(1, &self.framebuffer);
+            gl::DeleteRenderbuffers(1, &self.color);
+            gl::DeleteRenderbuffers(1, &self.depth_stencil);
+            self.framebuffer = 0;
+        }
+    }
+}
+
+impl Drop for FrameCache {
+    fn drop(&mut self) {
+        unsafe { self.release() }
+    }
+}
@@ -0,0 +1,134 @@
+use crate::Cell;
+use std::fmt;
+
+/// Well known rules that can be cycled through at runtime.
+pub const PRESETS: [&str; 5] = [
+    "B3/S23",         // Conway's Life
+    "B36/S23",        // HighLife
+    "B2/S",           // Seeds
+    "B3/S012345678",  // Life without Death
+    "B3678/S34678",   // Day & Night
+];
+
+/// An outer-totalistic Life-like rule: which neighbour counts cause a birth and which let a
+/// live cell survive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rule {
+    birth: [bool; 9],
+    survive: [bool; 9],
+}
+
+impl Rule {
+    pub fn conway() -> Rule {
+        return Rule::parse(PRESETS[0]).unwrap();
+    }
+
+    /// Parses `B3/S23` style rulestrings (either order, any case) as well as the older
+    /// `23/3` survive/birth notation.
+    pub fn parse(rulestring: &str) -> anyhow::Result<Rule> {
+        let parts: Vec<&str> = rulestring.trim().split('/').collect();
+        if parts.len() != 2 {
+            anyhow::bail!("rulestring {:?} must have exactly one '/'", rulestring);
+        }
+
+        let mut birth = None;
+        let mut survive = None;
+        for (i, part) in parts.iter().enumerate() {
+            let (target, digits) = match part.chars().next().map(|c| c.to_ascii_uppercase()) {
+                Some('B') => (&mut birth, &part[1..]),
+                Some('S') => (&mut survive, &part[1..]),
+                // Without prefixes the first half lists survival counts and the second births.
+                _ if i == 0 => (&mut survive, *part),
+                _ => (&mut birth, *part),
+            };
+            if target.is_some() {
+                anyhow::bail!("rulestring {:?} repeats a section", rulestring);
+            }
+            *target = Some(parse_counts(digits, rulestring)?);
+        }
+
+        match (birth, survive) {
+            (Some(birth), Some(survive)) => Ok(Rule { birth, survive }),
+            _ => anyhow::bail!("rulestring {:?} needs both a B and an S section", rulestring),
+        }
+    }
+
+    /// Whether dead cells with no live neighbours are born (B0), bringing empty space to life.
+    pub fn births_from_nothing(&self) -> bool {
+        return self.birth[0];
+    }
+
+    pub fn next_state(&self, cell: Cell, live_neighbors: u8) -> Cell {
+        let n = live_neighbors as usize;
+        let alive = match cell {
+            Cell::Alive => self.survive[n],
+            Cell::Dead => self.birth[n],
+        };
+        if alive {
+            Cell::Alive
+        } else {
+            Cell::Dead
+        }
+    }
+}
+
+fn parse_counts(digits: &str, rulestring: &str) -> anyhow::Result<[bool; 9]> {
+    let mut counts = [false; 9];
+    for c in digits.chars() {
+        match c.to_digit(10) {
+            Some(n) if n <= 8 => counts[n as usize] = true,
+            _ => anyhow::bail!("rulestring {:?} has invalid neighbour count {:?}", rulestring, c),
+        }
+    }
+    return Ok(counts);
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "B")?;
+        for n in (0..9).filter(|&n| self.birth[n]) {
+            write!(f, "{}", n)?;
+        }
+        write!(f, "/S")?;
+        for n in (0..9).filter(|&n| self.survive[n]) {
+            write!(f, "{}", n)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_birth_survive_notation() {
+        let highlife = Rule::parse("B36/S23").unwrap();
+        for n in 0..9 {
+            assert_eq!(highlife.birth[n], n == 3 || n == 6);
+            assert_eq!(highlife.survive[n], n == 2 || n == 3);
+        }
+        assert_eq!(Rule::parse("s23/b36").unwrap(), highlife);
+        assert_eq!(highlife.to_string(), "B36/S23");
+    }
+
+    #[test]
+    fn parses_survive_birth_notation() {
+        assert_eq!(Rule::parse("23/3").unwrap(), Rule::conway());
+        assert_eq!(Rule::parse("/2").unwrap(), Rule::parse("B2/S").unwrap());
+    }
+
+    #[test]
+    fn presets_round_trip_through_display() {
+        for preset in &PRESETS {
+            assert_eq!(Rule::parse(preset).unwrap().to_string(), *preset);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_rulestrings() {
+        for rulestring in &["", "B3S23", "B3/S23/S1", "B3/B6", "B3/S29", "B3/Sx", "B3/23"] {
+            assert!(Rule::parse(rulestring).is_err(), "{:?} should not parse", rulestring);
+        }
+    }
+}
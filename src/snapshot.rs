@@ -0,0 +1,76 @@
+use crate::engine::EngineKind;
+use crate::pattern::Pattern;
+use crate::rule::Rule;
+use crate::stats::Stats;
+use crate::{Boundary, RuleGradient, Universe};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Everything needed to resume a simulation, stored as JSON.
+///
+/// Unlike an exported RLE pattern this keeps the grid's size and position, the generation
+/// count and the seed, so noise carries on exactly as it would have. Cell ages are not saved;
+/// every cell starts out fresh.
+#[derive(Serialize, Deserialize)]
+struct SavedState {
+    width: u32,
+    height: u32,
+    /// Missing from older snapshots, which were all loaded into a dense grid by default.
+    #[serde(default)]
+    engine: EngineKind,
+    rule: String,
+    boundary: Boundary,
+    gradient: bool,
+    temperature: f32,
+    seed: u64,
+    generation: u64,
+    /// Live cells as an RLE pattern spanning the whole grid.
+    cells: String,
+}
+
+pub fn save<P: AsRef<Path>>(universe: &Universe, path: P) -> anyhow::Result<()> {
+    let pattern = Pattern {
+        name: None,
+        width: universe.width,
+        height: universe.height,
+        cells: universe.engine.live_cells(),
+        rule: None,
+    };
+    let state = SavedState {
+        width: universe.width,
+        height: universe.height,
+        engine: universe.engine.kind(),
+        rule: universe.rule.to_string(),
+        boundary: universe.boundary,
+        gradient: universe.gradient.is_some(),
+        temperature: universe.temperature,
+        seed: universe.seed,
+        generation: universe.stats.generation,
+        cells: pattern.to_rle(),
+    };
+    std::fs::write(path, serde_json::to_string_pretty(&state)?)?;
+    return Ok(());
+}
+
+/// Recreates a saved universe with the engine that saved it, unless `engine` asks for another.
+pub fn load<P: AsRef<Path>>(path: P, engine: Option<EngineKind>) -> anyhow::Result<Universe> {
+    let state: SavedState = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    let pattern = Pattern::from_rle(&state.cells)?;
+    if (pattern.width, pattern.height) != (state.width, state.height) {
+        anyhow::bail!(
+            "snapshot cells are {}x{} but the universe is {}x{}",
+            pattern.width, pattern.height, state.width, state.height,
+        );
+    }
+
+    let mut universe = Universe::new(state.width, state.height, engine.unwrap_or(state.engine))?;
+    universe.stamp(&pattern, 0, 0);
+    universe.set_rule(Rule::parse(&state.rule)?)?;
+    universe.boundary = state.boundary;
+    universe.gradient = if state.gradient { Some(RuleGradient::default()) } else { None };
+    universe.temperature = state.temperature;
+    universe.seed = state.seed;
+    universe.stats = Stats::new(universe.stats.population);
+    universe.stats.generation = state.generation;
+    return Ok(universe);
+}
@@ -0,0 +1,48 @@
+use std::collections::VecDeque;
+
+/// Number of past generations kept for the population graph.
+pub const POPULATION_HISTORY: usize = 300;
+
+/// Counters updated by `Universe::tick` so the HUD only has to read them.
+pub struct Stats {
+    pub generation: u64,
+    pub population: u32,
+    /// Cells born in the last tick.
+    pub births: u32,
+    /// Cells that died in the last tick.
+    pub deaths: u32,
+    /// Population after each of the most recent generations, oldest first.
+    pub history: VecDeque<u32>,
+}
+
+impl Stats {
+    pub fn new(population: u32) -> Stats {
+        return Stats {
+            generation: 0,
+            population,
+            births: 0,
+            deaths: 0,
+            history: VecDeque::with_capacity(POPULATION_HISTORY),
+        };
+    }
+
+    pub fn record_tick(&mut self, births: u32, deaths: u32) {
+        self.generation += 1;
+        self.births = births;
+        self.deaths = deaths;
+        self.population = self.population + births - deaths;
+        if self.history.len() == POPULATION_HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(self.population);
+    }
+
+    /// Undoes the bookkeeping of the last `record_tick` after the grid was stepped back.
+    pub fn rewind(&mut self, population: u32) {
+        self.generation = self.generation.saturating_sub(1);
+        self.population = population;
+        self.births = 0;
+        self.deaths = 0;
+        self.history.pop_back();
+    }
+}
@@ -0,0 +1,72 @@
+use gol_nvg::Cell;
+use nvg::Color;
+
+/// Generations after which a live cell is drawn in its theme's "old" color.
+const MATURE_AGE: u16 = 50;
+/// Generations a dead cell keeps a fading trail after it dies.
+const TRAIL_LENGTH: u16 = 8;
+
+/// Color schemes for cells, picked from a cell's state and age.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Theme {
+    /// Flat orange on black, ignoring age.
+    Classic,
+    /// Young cells glow yellow and cool to red as they settle; dead cells leave embers.
+    Heat,
+    /// Young cells are pale cyan and deepen to navy; dead cells leave a teal wake.
+    Ocean,
+}
+
+impl Theme {
+    pub fn next(self) -> Theme {
+        match self {
+            Theme::Classic => Theme::Heat,
+            Theme::Heat => Theme::Ocean,
+            Theme::Ocean => Theme::Classic,
+        }
+    }
+
+    /// Whether the cell's color changed with the generation that just passed, i.e. it is still
+    /// ageing through the gradient.
+    pub fn fades(self, cell: Cell, age: u16) -> bool {
+        match (self, cell) {
+            (Theme::Classic, _) => false,
+            (_, Cell::Alive) => age <= MATURE_AGE,
+            (_, Cell::Dead) => age <= TRAIL_LENGTH,
+        }
+    }
+
+    /// Whether the cell is drawn in the plain background color, so a freshly cleared frame
+    /// already shows it correctly.
+    pub fn is_background(self, cell: Cell, age: u16) -> bool {
+        match (self, cell) {
+            (_, Cell::Alive) => false,
+            (Theme::Classic, Cell::Dead) => true,
+            (_, Cell::Dead) => age >= TRAIL_LENGTH,
+        }
+    }
+
+    pub fn color(self, cell: Cell, age: u16) -> Color {
+        let (young, old, trail) = match self {
+            Theme::Classic => {
+                return match cell {
+                    Cell::Alive => Color::rgb_i(227, 183, 61),
+                    Cell::Dead => Color::rgba(0.0, 0.0, 0.0, 1.0),
+                };
+            }
+            Theme::Heat => ((255, 240, 120), (180, 30, 20), (90, 20, 10)),
+            Theme::Ocean => ((120, 255, 230), (20, 60, 160), (10, 60, 70)),
+        };
+
+        match cell {
+            Cell::Alive => mix(young, old, age.min(MATURE_AGE) as f32 / MATURE_AGE as f32),
+            Cell::Dead if age < TRAIL_LENGTH => mix(trail, (0, 0, 0), (age + 1) as f32 / TRAIL_LENGTH as f32),
+            Cell::Dead => Color::rgba(0.0, 0.0, 0.0, 1.0),
+        }
+    }
+}
+
+fn mix(from: (u8, u8, u8), to: (u8, u8, u8), t: f32) -> Color {
+    let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t) / 255.0;
+    return Color::rgb(channel(from.0, to.0), channel(from.1, to.1), channel(from.2, to.2));
+}